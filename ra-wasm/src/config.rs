@@ -0,0 +1,295 @@
+//! Analysis configuration, set from JavaScript through `WorldState::set_config`.
+//!
+//! Every field has a default, so the front end only needs to pass the options
+//! it wants to change. The config outlives `WorldState::init`.
+use std::collections::{BTreeMap, BTreeSet};
+
+use ide::{Snippet, SnippetScope};
+use ide_db::helpers::{
+    insert_use::{ImportGranularity, InsertUseConfig, PrefixKind},
+    SnippetCap,
+};
+use serde::Deserialize;
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub completion: CompletionConfig,
+    pub inlay_hints: InlayHintsConfig,
+    pub diagnostics: DiagnosticsConfig,
+    pub join_lines: JoinLinesConfig,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct CompletionConfig {
+    pub postfix: bool,
+    pub imports_on_the_fly: bool,
+    pub self_on_the_fly: bool,
+    pub call_parenthesis: bool,
+    pub call_argument_snippets: bool,
+    pub import_granularity: ImportGranularityDef,
+    pub import_enforce_granularity: bool,
+    pub import_prefix: ImportPrefixDef,
+    pub import_group: bool,
+    /// Custom snippets, keyed by name, in the same format as the
+    /// `rust-analyzer.completion.snippets` setting of the desktop extension.
+    pub snippets: BTreeMap<String, SnippetDef>,
+}
+
+impl Default for CompletionConfig {
+    fn default() -> Self {
+        Self {
+            postfix: true,
+            imports_on_the_fly: true,
+            self_on_the_fly: true,
+            call_parenthesis: true,
+            call_argument_snippets: true,
+            import_granularity: ImportGranularityDef::Module,
+            import_enforce_granularity: false,
+            import_prefix: ImportPrefixDef::Plain,
+            import_group: true,
+            snippets: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct InlayHintsConfig {
+    pub type_hints: bool,
+    pub parameter_hints: bool,
+    pub chaining_hints: bool,
+    pub max_length: Option<usize>,
+    /// Whether type and parameter hints get a `:` separating them from the
+    /// code, otherwise they are only padded with whitespace.
+    pub render_colons: bool,
+    /// Return types of closures with a block body and no annotation.
+    pub closure_return_type_hints: bool,
+    pub lifetime_elision_hints: LifetimeElisionDef,
+}
+
+impl Default for InlayHintsConfig {
+    fn default() -> Self {
+        Self {
+            type_hints: true,
+            parameter_hints: true,
+            chaining_hints: true,
+            max_length: Some(25),
+            render_colons: true,
+            closure_return_type_hints: false,
+            lifetime_elision_hints: LifetimeElisionDef::Never,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LifetimeElisionDef {
+    Never,
+    Always,
+    /// Only when the return type has elided lifetimes.
+    SkipTrivial,
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct DiagnosticsConfig {
+    pub experimental: bool,
+    /// Codes of the diagnostics that are not reported, e.g.
+    /// `["unresolved-import"]` to silence imports the bundled sysroot lacks.
+    pub disabled: BTreeSet<String>,
+    /// Severity overrides, keyed by diagnostic code, e.g.
+    /// `{ "unresolved-macro-call": "hint" }`.
+    pub severity: BTreeMap<String, SeverityDef>,
+}
+
+impl Default for DiagnosticsConfig {
+    fn default() -> Self {
+        Self { experimental: true, disabled: BTreeSet::new(), severity: BTreeMap::new() }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(default)]
+pub struct JoinLinesConfig {
+    pub join_else_if: bool,
+    pub remove_trailing_comma: bool,
+    pub unwrap_trivial_blocks: bool,
+    pub join_assignments: bool,
+}
+
+impl Default for JoinLinesConfig {
+    fn default() -> Self {
+        Self {
+            join_else_if: true,
+            remove_trailing_comma: true,
+            unwrap_trivial_blocks: true,
+            join_assignments: true,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SeverityDef {
+    Hint,
+    Info,
+    Warning,
+    Error,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportGranularityDef {
+    Preserve,
+    Item,
+    Crate,
+    Module,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportPrefixDef {
+    Plain,
+    #[serde(alias = "self")]
+    BySelf,
+    #[serde(alias = "crate")]
+    ByCrate,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SnippetDef {
+    pub prefix: SingleOrVec,
+    pub postfix: SingleOrVec,
+    pub body: SingleOrVec,
+    pub requires: SingleOrVec,
+    pub description: Option<String>,
+    pub scope: SnippetScopeDef,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum SingleOrVec {
+    Single(String),
+    Vec(Vec<String>),
+}
+
+impl Default for SingleOrVec {
+    fn default() -> Self {
+        SingleOrVec::Vec(Vec::new())
+    }
+}
+
+impl SingleOrVec {
+    fn as_slice(&self) -> &[String] {
+        match self {
+            SingleOrVec::Single(it) => std::slice::from_ref(it),
+            SingleOrVec::Vec(it) => it,
+        }
+    }
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SnippetScopeDef {
+    Expr,
+    Item,
+    Type,
+}
+
+impl Default for SnippetScopeDef {
+    fn default() -> Self {
+        SnippetScopeDef::Expr
+    }
+}
+
+impl Config {
+    pub fn completion(&self) -> ide::CompletionConfig {
+        let c = &self.completion;
+        ide::CompletionConfig {
+            enable_postfix_completions: c.postfix,
+            enable_imports_on_the_fly: c.imports_on_the_fly,
+            enable_self_on_the_fly: c.self_on_the_fly,
+            add_call_parenthesis: c.call_parenthesis,
+            add_call_argument_snippets: c.call_argument_snippets,
+            snippet_cap: SnippetCap::new(true),
+            insert_use: self.insert_use(),
+            snippets: self.snippets(),
+        }
+    }
+
+    pub fn diagnostics(&self) -> ide::DiagnosticsConfig {
+        ide::DiagnosticsConfig {
+            disable_experimental: !self.diagnostics.experimental,
+            disabled: self.diagnostics.disabled.iter().cloned().collect(),
+        }
+    }
+
+    pub fn inlay_hints(&self) -> ide::InlayHintsConfig {
+        let c = &self.inlay_hints;
+        ide::InlayHintsConfig {
+            type_hints: c.type_hints,
+            parameter_hints: c.parameter_hints,
+            chaining_hints: c.chaining_hints,
+            max_length: c.max_length,
+        }
+    }
+
+    pub fn join_lines(&self) -> ide::JoinLinesConfig {
+        let c = &self.join_lines;
+        ide::JoinLinesConfig {
+            join_else_if: c.join_else_if,
+            remove_trailing_comma: c.remove_trailing_comma,
+            unwrap_trivial_blocks: c.unwrap_trivial_blocks,
+            join_assignments: c.join_assignments,
+        }
+    }
+
+    fn snippets(&self) -> Vec<Snippet> {
+        self.completion
+            .snippets
+            .iter()
+            .filter_map(|(name, def)| {
+                let scope = match def.scope {
+                    SnippetScopeDef::Expr => SnippetScope::Expr,
+                    SnippetScopeDef::Item => SnippetScope::Item,
+                    SnippetScopeDef::Type => SnippetScope::Type,
+                };
+                let snippet = Snippet::new(
+                    def.prefix.as_slice(),
+                    def.postfix.as_slice(),
+                    def.body.as_slice(),
+                    def.description.as_ref().unwrap_or(name),
+                    def.requires.as_slice(),
+                    scope,
+                );
+                if snippet.is_none() {
+                    log::warn!("invalid snippet {}", name);
+                }
+                snippet
+            })
+            .collect()
+    }
+
+    fn insert_use(&self) -> InsertUseConfig {
+        let c = &self.completion;
+        InsertUseConfig {
+            granularity: match c.import_granularity {
+                ImportGranularityDef::Preserve => ImportGranularity::Preserve,
+                ImportGranularityDef::Item => ImportGranularity::Item,
+                ImportGranularityDef::Crate => ImportGranularity::Crate,
+                ImportGranularityDef::Module => ImportGranularity::Module,
+            },
+            enforce_granularity: c.import_enforce_granularity,
+            prefix_kind: match c.import_prefix {
+                ImportPrefixDef::Plain => PrefixKind::Plain,
+                ImportPrefixDef::BySelf => PrefixKind::BySelf,
+                ImportPrefixDef::ByCrate => PrefixKind::ByCrate,
+            },
+            group: c.import_group,
+            skip_glob_imports: false,
+        }
+    }
+}
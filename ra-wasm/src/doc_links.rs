@@ -0,0 +1,381 @@
+//! Resolution of intra-doc links in hover and completion documentation.
+//!
+//! rust-analyzer turns the links it can resolve into docs.rs URLs. For the
+//! sysroot crates those are pointed at doc.rust-lang.org instead. Links into
+//! the user crate (which is not published anywhere) and links rust-analyzer
+//! left unresolved are turned into `command:` URIs that navigate to the source
+//! of the linked item, which may live in the bundled sysroot.
+use ide::NavigationTarget;
+use ide_db::symbol_index::Query;
+use syntax::{
+    ast::{self, HasName},
+    AstNode, NodeOrToken,
+};
+
+use crate::{return_types::MarkdownTrust, WorldState};
+
+pub(crate) const GOTO_LOCATION_COMMAND: &str = "rust-analyzer.gotoLocation";
+
+const SYSROOT_CRATES: &[&str] = &["std", "core", "alloc"];
+const SYSROOT_DOCS: &str = "https://doc.rust-lang.org/nightly/";
+const DOCS_RS: &str = "https://docs.rs/";
+
+/// Maps a documentation URL produced by rust-analyzer to one the browser can
+/// open, or `None` if the URL points at the (unpublished) user crate.
+pub(crate) fn external_url(url: &str) -> Option<String> {
+    let rest = match url.strip_prefix(DOCS_RS) {
+        Some(rest) => rest,
+        None => return Some(url.to_string()),
+    };
+    let (krate, path) = rest.split_once("/*/")?;
+    if SYSROOT_CRATES.contains(&krate) {
+        Some(format!("{}{}", SYSROOT_DOCS, path))
+    } else if krate == crate::LOCAL_CRATE_NAME {
+        None
+    } else {
+        Some(url.to_string())
+    }
+}
+
+/// Lets the links produced by `rewrite_links` run, but no other command.
+pub(crate) fn trust() -> Option<MarkdownTrust> {
+    Some(MarkdownTrust { enabledCommands: vec![GOTO_LOCATION_COMMAND] })
+}
+
+/// Rewrites the targets of all markdown links in `markdown`, leaving code
+/// blocks and inline code untouched.
+pub(crate) fn rewrite_links(world: &WorldState, markdown: &str) -> String {
+    let definitions: Vec<&str> =
+        markdown.lines().filter_map(|line| link_definition(line).map(|(label, _)| label)).collect();
+    let resolve = |target: &str| resolve_link(world, target);
+
+    let mut res = String::with_capacity(markdown.len());
+    let mut in_code_block = false;
+    for line in markdown.split_inclusive('\n') {
+        if line.trim_start().starts_with("```") {
+            in_code_block ^= true;
+            res.push_str(line);
+            continue;
+        }
+        if in_code_block {
+            res.push_str(line);
+            continue;
+        }
+        match link_definition(line) {
+            Some((label, target)) => match resolve(target) {
+                Some(target) => {
+                    res.push_str(&format!("[{}]: {}", label, target));
+                    if line.ends_with('\n') {
+                        res.push('\n');
+                    }
+                }
+                None => res.push_str(line),
+            },
+            None => rewrite_inline_links(line, &definitions, &resolve, &mut res),
+        }
+    }
+    res
+}
+
+fn resolve_link(world: &WorldState, target: &str) -> Option<String> {
+    if target.contains("://") {
+        if let Some(url) = external_url(target) {
+            return (url != target).then(|| url);
+        }
+        return goto_location_uri(world, &path_from_url(target)?);
+    }
+    goto_location_uri(world, &path_from_link(target)?)
+}
+
+fn goto_location_uri(world: &WorldState, path: &[String]) -> Option<String> {
+    let nav = find_symbol(world, path)?;
+    let args = serde_wasm_bindgen::to_value(&[world.location(&nav)]).ok()?;
+    let args = String::from(js_sys::JSON::stringify(&args).ok()?);
+    let args = String::from(js_sys::encode_uri_component(&args));
+    Some(format!("command:{}?{}", GOTO_LOCATION_COMMAND, args))
+}
+
+/// Looks the item up in the user crate first, then in the sysroot. Items are
+/// found by name, so they must also be in a container named like the segment
+/// before it, see `container_name`, unless that's `crate`, `self`, `super` or
+/// `Self`. A single segment before it may name a crate, whose items have no
+/// container.
+fn find_symbol(world: &WorldState, path: &[String]) -> Option<NavigationTarget> {
+    let (name, qualifier) = path.split_last()?;
+    let in_container = |nav: &NavigationTarget| match qualifier.last().map(String::as_str) {
+        None | Some("crate") | Some("self") | Some("super") | Some("Self") => true,
+        Some(container) => match container_name(world, nav) {
+            Some(it) => it == container,
+            None => qualifier.len() == 1,
+        },
+    };
+    let search = |libs: bool| {
+        let mut query = Query::new(name.to_string());
+        query.exact();
+        if libs {
+            query.libs();
+        }
+        world.analysis().symbol_search(query).ok()?.into_iter().find(|nav| in_container(nav))
+    };
+    search(false).or_else(|| search(true))
+}
+
+/// The name a path would qualify the item of `nav` with: that of the module,
+/// trait or enum it's in, or of the `Self` type of its impl. The symbol index
+/// doesn't know about impls, so this looks at the syntax.
+fn container_name(world: &WorldState, nav: &NavigationTarget) -> Option<String> {
+    let file = world.analysis().parse(nav.file_id).ok()?;
+    let item = match file.syntax().covering_element(nav.full_range) {
+        NodeOrToken::Node(it) => it,
+        NodeOrToken::Token(it) => it.parent()?,
+    };
+    item.ancestors().skip(1).find_map(|node| {
+        let name = match ast::Item::cast(node)? {
+            ast::Item::Impl(it) => match it.self_ty()? {
+                ast::Type::PathType(it) => it.path()?.segment()?.name_ref()?.syntax().clone(),
+                _ => return None,
+            },
+            ast::Item::Module(it) => it.name()?.syntax().clone(),
+            ast::Item::Trait(it) => it.name()?.syntax().clone(),
+            ast::Item::Enum(it) => it.name()?.syntax().clone(),
+            _ => return None,
+        };
+        Some(name.text().to_string())
+    })
+}
+
+/// Extracts the path of the item from a rustdoc URL such as
+/// `https://docs.rs/my_crate/*/my_crate/struct.Foo.html#method.bar`.
+fn path_from_url(url: &str) -> Option<Vec<String>> {
+    let (path, fragment) = match url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (url, None),
+    };
+    let (_, path) = path.split_once("/*/")?;
+    let mut segments: Vec<String> = path.split('/').map(str::to_string).collect();
+    let file = segments.pop()?;
+    // Modules are documented in their `index.html`.
+    if let Some((_kind, name)) = file.strip_suffix(".html")?.split_once('.') {
+        segments.push(name.to_string());
+    }
+    if let Some((_kind, name)) = fragment.and_then(|it| it.split_once('.')) {
+        segments.push(name.to_string());
+    }
+    Some(segments)
+}
+
+/// Extracts the path of the item from an intra-doc link such as
+/// `` `crate::Foo::bar()` ``.
+fn path_from_link(link: &str) -> Option<Vec<String>> {
+    let path = link.trim().trim_matches('`');
+    let path = match path.split_once('@') {
+        Some((_disambiguator, path)) => path,
+        None => path,
+    };
+    let path = path.trim_end_matches("()").trim_end_matches('!');
+    let is_ident = |s: &str| {
+        !s.is_empty()
+            && !s.starts_with(|c: char| c.is_ascii_digit())
+            && s.chars().all(|c| c.is_alphanumeric() || c == '_')
+    };
+    if !path.split("::").all(is_ident) {
+        return None;
+    }
+    Some(path.split("::").map(str::to_string).collect())
+}
+
+/// Parses a link reference definition, `[label]: target`.
+fn link_definition(line: &str) -> Option<(&str, &str)> {
+    let rest = line.trim_start().strip_prefix('[')?;
+    let (label, rest) = rest.split_once("]:")?;
+    let target = rest.split_whitespace().next()?;
+    Some((label, target))
+}
+
+fn rewrite_inline_links(
+    line: &str,
+    definitions: &[&str],
+    resolve: &impl Fn(&str) -> Option<String>,
+    res: &mut String,
+) {
+    let mut rest = line;
+    while let Some(idx) = rest.find(|c: char| c == '`' || c == '[') {
+        let (before, tail) = rest.split_at(idx);
+        res.push_str(before);
+
+        if tail.starts_with('`') {
+            let ticks = tail.len() - tail.trim_start_matches('`').len();
+            let end = match tail[ticks..].find(&tail[..ticks]) {
+                Some(end) => 2 * ticks + end,
+                None => tail.len(),
+            };
+            res.push_str(&tail[..end]);
+            rest = &tail[end..];
+            continue;
+        }
+
+        let text_end = match closing_delimiter(tail, '[', ']') {
+            Some(it) => it,
+            None => {
+                res.push_str(tail);
+                return;
+            }
+        };
+        let text = &tail[1..text_end];
+        let after = &tail[text_end + 1..];
+
+        if after.starts_with('(') {
+            // Inline link, `[text](target "title")`.
+            if let Some(end) = closing_delimiter(after, '(', ')') {
+                let inner = &after[1..end];
+                let target = inner.split_whitespace().next().unwrap_or_default();
+                match resolve(target) {
+                    Some(new_target) => res.push_str(&format!(
+                        "[{}]({})",
+                        text,
+                        inner.replacen(target, &new_target, 1)
+                    )),
+                    None => res.push_str(&tail[..text_end + 1 + end + 1]),
+                }
+                rest = &after[end + 1..];
+                continue;
+            }
+        } else if after.starts_with('[') {
+            // Reference link, `[text][label]` or `[text][]`.
+            if let Some(end) = closing_delimiter(after, '[', ']') {
+                let label = match &after[1..end] {
+                    "" => text,
+                    label => label,
+                };
+                let target = if definitions.contains(&label) { None } else { resolve(label) };
+                match target {
+                    Some(target) => res.push_str(&format!("[{}]({})", text, target)),
+                    None => res.push_str(&tail[..text_end + 1 + end + 1]),
+                }
+                rest = &after[end + 1..];
+                continue;
+            }
+        } else if !after.starts_with(':') && !definitions.contains(&text) {
+            // Shortcut link, `[text]`.
+            if let Some(target) = resolve(text) {
+                res.push_str(&format!("[{}]({})", text, target));
+                rest = after;
+                continue;
+            }
+        }
+
+        res.push_str(&tail[..text_end + 1]);
+        rest = after;
+    }
+    res.push_str(rest);
+}
+
+/// Returns the offset of the delimiter closing the one `s` starts with.
+fn closing_delimiter(s: &str, open: char, close: char) -> Option<usize> {
+    let mut depth = 0;
+    for (idx, c) in s.char_indices() {
+        if c == open {
+            depth += 1;
+        } else if c == close {
+            depth -= 1;
+            if depth == 0 {
+                return Some(idx);
+            }
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn path(segments: &[&str]) -> Option<Vec<String>> {
+        Some(segments.iter().map(|it| it.to_string()).collect())
+    }
+
+    /// Rewrites `line`, resolving links to items named `Foo...` only.
+    fn rewrite(line: &str, definitions: &[&str]) -> String {
+        let resolve = |target: &str| {
+            target.trim_matches('`').starts_with("Foo").then(|| format!("to:{}", target))
+        };
+        let mut res = String::new();
+        rewrite_inline_links(line, definitions, &resolve, &mut res);
+        res
+    }
+
+    #[test]
+    fn external_urls() {
+        assert_eq!(
+            external_url("https://docs.rs/core/*/core/option/enum.Option.html").unwrap(),
+            "https://doc.rust-lang.org/nightly/core/option/enum.Option.html"
+        );
+        assert_eq!(external_url("https://docs.rs/my_crate/*/my_crate/struct.Foo.html"), None);
+        let url = "https://docs.rs/serde/*/serde/trait.Serialize.html";
+        assert_eq!(external_url(url).unwrap(), url);
+        assert_eq!(external_url("https://example.com/").unwrap(), "https://example.com/");
+    }
+
+    #[test]
+    fn paths_from_urls() {
+        let url = "https://docs.rs/my_crate/*/my_crate/struct.Foo.html#method.bar";
+        assert_eq!(path_from_url(url), path(&["my_crate", "Foo", "bar"]));
+        let url = "https://docs.rs/my_crate/*/my_crate/a/fn.f.html";
+        assert_eq!(path_from_url(url), path(&["my_crate", "a", "f"]));
+        let url = "https://docs.rs/my_crate/*/my_crate/a/index.html";
+        assert_eq!(path_from_url(url), path(&["my_crate", "a"]));
+        assert_eq!(path_from_url("https://docs.rs/my_crate/"), None);
+    }
+
+    #[test]
+    fn paths_from_links() {
+        assert_eq!(path_from_link("`crate::Foo::bar()`"), path(&["crate", "Foo", "bar"]));
+        assert_eq!(path_from_link("fn@bar"), path(&["bar"]));
+        assert_eq!(path_from_link("vec!"), path(&["vec"]));
+        assert_eq!(path_from_link("Vec<T>"), None);
+        assert_eq!(path_from_link("https://example.com"), None);
+        assert_eq!(path_from_link("a::0"), None);
+    }
+
+    #[test]
+    fn link_definitions() {
+        assert_eq!(link_definition("[Foo]: crate::Foo \"title\""), Some(("Foo", "crate::Foo")));
+        assert_eq!(link_definition("  [a b]: Foo"), Some(("a b", "Foo")));
+        assert_eq!(link_definition("[Foo] and [Bar]"), None);
+        assert_eq!(link_definition("[Foo]:"), None);
+    }
+
+    #[test]
+    fn closing_delimiters() {
+        assert_eq!(closing_delimiter("[a[b]c]d", '[', ']'), Some(6));
+        assert_eq!(closing_delimiter("(a(b)", '(', ')'), None);
+    }
+
+    #[test]
+    fn inline_links() {
+        assert_eq!(rewrite("see [x](Foo) and [y](Bar)", &[]), "see [x](to:Foo) and [y](Bar)");
+        assert_eq!(rewrite("[x](Foo \"title\")", &[]), "[x](to:Foo \"title\")");
+        assert_eq!(rewrite("[x](Foo::f(a))", &[]), "[x](to:Foo::f(a))");
+    }
+
+    #[test]
+    fn reference_links() {
+        assert_eq!(rewrite("[x][Foo] [Foo][]", &[]), "[x](to:Foo) [Foo](to:Foo)");
+        assert_eq!(rewrite("[x][Foo]", &["Foo"]), "[x][Foo]");
+    }
+
+    #[test]
+    fn shortcut_links() {
+        assert_eq!(rewrite("a [Foo] b", &[]), "a [Foo](to:Foo) b");
+        assert_eq!(rewrite("a [`Foo`]", &[]), "a [`Foo`](to:`Foo`)");
+        assert_eq!(rewrite("a [Foo] b", &["Foo"]), "a [Foo] b");
+        assert_eq!(rewrite("[Bar] [Foo", &[]), "[Bar] [Foo");
+    }
+
+    #[test]
+    fn code_spans_are_untouched() {
+        assert_eq!(rewrite("`[Foo]` [Foo]", &[]), "`[Foo]` [Foo](to:Foo)");
+        assert_eq!(rewrite("``a ` [Foo]`` x", &[]), "``a ` [Foo]`` x");
+        assert_eq!(rewrite("`[Foo]", &[]), "`[Foo]");
+    }
+}
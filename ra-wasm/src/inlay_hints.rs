@@ -0,0 +1,269 @@
+//! Inlay hints of a file: those computed by `ide`, plus closure return type
+//! and lifetime elision hints, which the pinned `ra_ap_ide` lacks.
+//!
+//! Reborrow and binding mode hints are not provided, they need the type
+//! adjustments of expressions and patterns, which its `hir` doesn't expose.
+//!
+//! Its `inlay_hints` has no range parameter, so its hints are always computed
+//! for the whole file. Only the syntax walk for ours is limited to the range.
+use hir::{HirDisplay, Semantics};
+use ide::{AnalysisHost, FileId, TextRange};
+use ide_db::RootDatabase;
+use syntax::{
+    ast::{self, HasName},
+    AstNode, SyntaxNode, SyntaxToken, WalkEvent,
+};
+
+use crate::config::{Config, LifetimeElisionDef};
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InlayKind {
+    Type,
+    Parameter,
+    Chaining,
+    ClosureReturnType,
+    /// A lifetime after a `&`, or in front of existing generic parameters.
+    Lifetime,
+    /// The elided lifetimes of a function without generic parameters.
+    GenericParamList,
+}
+
+pub(crate) struct InlayHint {
+    pub(crate) kind: InlayKind,
+    /// Parameter hints go in front of it, all others after it.
+    pub(crate) range: TextRange,
+    pub(crate) label: String,
+}
+
+/// The hints intersecting `range`, or those of the whole file if `None`.
+pub(crate) fn inlay_hints(
+    host: &AnalysisHost,
+    file_id: FileId,
+    range: Option<TextRange>,
+    config: &Config,
+) -> Vec<InlayHint> {
+    let in_range = |it: TextRange| range.map_or(true, |range| it.intersect(range).is_some());
+    let mut res: Vec<InlayHint> = host
+        .analysis()
+        .inlay_hints(&config.inlay_hints(), file_id)
+        .unwrap()
+        .into_iter()
+        .map(|hint| InlayHint {
+            kind: match hint.kind {
+                ide::InlayKind::TypeHint => InlayKind::Type,
+                ide::InlayKind::ParameterHint => InlayKind::Parameter,
+                ide::InlayKind::ChainingHint => InlayKind::Chaining,
+            },
+            range: hint.range,
+            label: hint.label.to_string(),
+        })
+        .filter(|hint| in_range(hint.range))
+        .collect();
+
+    let c = &config.inlay_hints;
+    if !c.closure_return_type_hints && c.lifetime_elision_hints == LifetimeElisionDef::Never {
+        return res;
+    }
+    let sema = Semantics::new(host.raw_database());
+    let file = sema.parse(file_id);
+    let mut preorder = file.syntax().preorder();
+    while let Some(event) = preorder.next() {
+        let node = match event {
+            WalkEvent::Enter(node) => node,
+            WalkEvent::Leave(_) => continue,
+        };
+        if !in_range(node.text_range()) {
+            preorder.skip_subtree();
+            continue;
+        }
+        if let Some(closure) = ast::ClosureExpr::cast(node.clone()) {
+            if c.closure_return_type_hints {
+                res.extend(closure_return_type_hint(&sema, &closure, c.max_length));
+            }
+        } else if let Some(func) = ast::Fn::cast(node) {
+            if c.lifetime_elision_hints != LifetimeElisionDef::Never {
+                lifetime_elision_hints(&func, c.lifetime_elision_hints, &mut res);
+            }
+        }
+    }
+    res.retain(|hint| in_range(hint.range));
+    res
+}
+
+/// Like in rust-analyzer, only closures with a block body get a hint.
+fn closure_return_type_hint(
+    sema: &Semantics<RootDatabase>,
+    closure: &ast::ClosureExpr,
+    max_length: Option<usize>,
+) -> Option<InlayHint> {
+    if closure.ret_type().is_some() || !matches!(closure.body()?, ast::Expr::BlockExpr(_)) {
+        return None;
+    }
+    let ty = sema.type_of_expr(&ast::Expr::ClosureExpr(closure.clone()))?.original;
+    let ret_type = ty.as_callable(sema.db)?.return_type();
+    if ret_type.is_unknown() {
+        return None;
+    }
+    Some(InlayHint {
+        kind: InlayKind::ClosureReturnType,
+        range: closure.param_list()?.syntax().text_range(),
+        label: format!("-> {}", ret_type.display_truncated(sema.db, max_length)),
+    })
+}
+
+/// Names the elided lifetimes of `func` `'0`, `'1`, ... and shows them after
+/// each `&`, in the generic parameters, and in the return type where the
+/// elision rules assign it one of the input lifetimes.
+fn lifetime_elision_hints(
+    func: &ast::Fn,
+    mode: LifetimeElisionDef,
+    acc: &mut Vec<InlayHint>,
+) -> Option<()> {
+    let param_list = func.param_list()?;
+    let mut hints = Vec::new();
+    let mut fresh = Vec::new();
+    let mut named = Vec::new();
+    let mut anonymous = 0;
+
+    let mut self_lifetime = None;
+    if let Some(self_param) = param_list.self_param() {
+        if let Some(amp) = self_param.amp_token() {
+            self_lifetime = Some(match self_param.lifetime() {
+                Some(lifetime) => lifetime.syntax().text().to_string(),
+                None => push_fresh(&amp, &mut fresh, &mut hints),
+            });
+        }
+    }
+    for ty in param_list.params().filter_map(|it| it.ty()) {
+        for node in elision_scope(&ty) {
+            if let Some(lifetime) = ast::Lifetime::cast(node.clone()) {
+                let name = lifetime.syntax().text().to_string();
+                if name == "'_" {
+                    anonymous += 1;
+                } else if name != "'static" && !named.contains(&name) {
+                    named.push(name);
+                }
+            } else if let Some(ref_type) = ast::RefType::cast(node) {
+                if let (None, Some(amp)) = (ref_type.lifetime(), ref_type.amp_token()) {
+                    push_fresh(&amp, &mut fresh, &mut hints);
+                }
+            }
+        }
+    }
+
+    let output = match self_lifetime {
+        Some(it) => Some(it),
+        None if fresh.len() + named.len() + anonymous == 1 => {
+            fresh.first().or_else(|| named.first()).cloned()
+        }
+        None => None,
+    };
+    let mut has_output_hints = false;
+    if let (Some(output), Some(ty)) = (output, func.ret_type().and_then(|it| it.ty())) {
+        for ref_type in elision_scope(&ty).filter_map(ast::RefType::cast) {
+            if let (None, Some(amp)) = (ref_type.lifetime(), ref_type.amp_token()) {
+                let range = amp.text_range();
+                hints.push(InlayHint { kind: InlayKind::Lifetime, range, label: output.clone() });
+                has_output_hints = true;
+            }
+        }
+    }
+    if mode == LifetimeElisionDef::SkipTrivial && !has_output_hints {
+        return None;
+    }
+
+    if !fresh.is_empty() {
+        let names = fresh.join(", ");
+        hints.push(match func.generic_param_list() {
+            Some(generics) => InlayHint {
+                kind: InlayKind::Lifetime,
+                range: generics.l_angle_token()?.text_range(),
+                label: format!("{},", names),
+            },
+            None => InlayHint {
+                kind: InlayKind::GenericParamList,
+                range: func.name()?.syntax().text_range(),
+                label: format!("<{}>", names),
+            },
+        });
+    }
+    acc.extend(hints);
+    Some(())
+}
+
+fn push_fresh(amp: &SyntaxToken, fresh: &mut Vec<String>, hints: &mut Vec<InlayHint>) -> String {
+    let name = format!("'{}", fresh.len());
+    let range = amp.text_range();
+    hints.push(InlayHint { kind: InlayKind::Lifetime, range, label: name.clone() });
+    fresh.push(name.clone());
+    name
+}
+
+/// The nodes of `ty` outside of function pointer and `Fn` trait types, which
+/// have elision rules of their own.
+fn elision_scope(ty: &ast::Type) -> impl Iterator<Item = SyntaxNode> + '_ {
+    ty.syntax().descendants().filter(move |node| {
+        !node
+            .ancestors()
+            .take_while(|it| it != ty.syntax())
+            .any(|it| ast::FnPtrType::can_cast(it.kind()) || ast::ParamList::can_cast(it.kind()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Renders the lifetime hints of the function in `text` into it.
+    fn check(mode: LifetimeElisionDef, text: &str, expect: &str) {
+        let file = syntax::SourceFile::parse(text).tree();
+        let func = file.syntax().descendants().find_map(ast::Fn::cast).unwrap();
+        let mut hints = Vec::new();
+        lifetime_elision_hints(&func, mode, &mut hints);
+        hints.sort_by_key(|it| std::cmp::Reverse(it.range.end()));
+
+        let mut res = text.to_string();
+        for hint in hints {
+            let label = match hint.kind {
+                InlayKind::Lifetime => format!("{} ", hint.label),
+                _ => hint.label,
+            };
+            res.insert_str(usize::from(hint.range.end()), &label);
+        }
+        assert_eq!(res, expect);
+    }
+
+    #[test]
+    fn lifetime_elision() {
+        check(
+            LifetimeElisionDef::Always,
+            "fn f(a: &u32, b: &mut u32) {}",
+            "fn f<'0, '1>(a: &'0 u32, b: &'1 mut u32) {}",
+        );
+        check(
+            LifetimeElisionDef::Always,
+            "fn f<T>(a: &T) -> &T {}",
+            "fn f<'0, T>(a: &'0 T) -> &'0 T {}",
+        );
+        check(
+            LifetimeElisionDef::Always,
+            "fn f(&self, a: &u32) -> &u32 {}",
+            "fn f<'0, '1>(&'0 self, a: &'1 u32) -> &'0 u32 {}",
+        );
+        check(
+            LifetimeElisionDef::Always,
+            "fn f<'a>(a: &'a u32, g: fn(&u32)) -> &u32 {}",
+            "fn f<'a>(a: &'a u32, g: fn(&u32)) -> &'a u32 {}",
+        );
+    }
+
+    #[test]
+    fn lifetime_elision_skips_trivial() {
+        check(LifetimeElisionDef::SkipTrivial, "fn f(a: &u32) {}", "fn f(a: &u32) {}");
+        check(
+            LifetimeElisionDef::SkipTrivial,
+            "fn f(a: &u32) -> &u32 {}",
+            "fn f<'0>(a: &'0 u32) -> &'0 u32 {}",
+        );
+    }
+}
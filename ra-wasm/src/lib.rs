@@ -1,34 +1,49 @@
-#![cfg(target_arch = "wasm32")]
+// Also built natively for `cargo test --target <host>`.
+#![cfg(any(target_arch = "wasm32", test))]
 #![allow(non_snake_case)]
 
-use std::sync::Arc;
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
 
 use cfg::CfgOptions;
 use ide::{
-    Analysis, AnalysisHost, Change, CompletionConfig, CrateGraph, CrateId, DiagnosticsConfig,
-    Edition, FileId, FilePosition, HoverConfig, HoverDocFormat, Indel, InlayHintsConfig, InlayKind,
-    SourceRoot, TextSize,
+    Analysis, AnalysisHost, Change, CrateGraph, CrateId, Edition, FileId, FilePosition,
+    FileSystemEdit, HoverConfig, HoverDocFormat, NavigationTarget, SourceRoot, TextSize,
 };
 use ide_db::{
-    base_db::{CrateName, Dependency, Env, FileSet, VfsPath},
-    helpers::{
-        insert_use::{ImportGranularity, InsertUseConfig, PrefixKind},
-        SnippetCap,
+    base_db::{
+        AnchoredPathBuf, CrateDisplayName, CrateName, Dependency, Env, FileSet, SourceDatabase,
+        SourceDatabaseExt, VfsPath,
     },
     search::SearchScope,
 };
+use once_cell::unsync::OnceCell;
+use syntax::{algo::find_node_at_offset, ast, AstNode};
 use wasm_bindgen::prelude::*;
 
+mod config;
+mod doc_links;
+mod inlay_hints;
+mod logger;
+mod navigation;
+mod snapshot;
+mod timings;
 mod to_proto;
 
 mod return_types;
 use return_types::*;
 
+pub use logger::set_log_callback;
+#[cfg(target_arch = "wasm32")]
 pub use wasm_bindgen_rayon::init_thread_pool;
 
 #[wasm_bindgen(start)]
 pub fn start() {
     console_error_panic_hook::set_once();
+    logger::init();
     log::info!("worker initialized")
 }
 
@@ -36,15 +51,48 @@ pub fn start() {
 pub struct WorldState {
     host: AnalysisHost,
     file_id: FileId,
+    config: config::Config,
+    /// The config in JSON, as passed to `set_config`, for `export_state`.
+    raw_config: Option<String>,
+    /// Bumped on every change to the analysed files.
+    revision: u64,
+    completions: Option<CompletionList>,
+    inlay_hints: Option<InlayHintCache>,
+    timings: timings::Timings,
+}
+
+/// The inlay hints of the ranges requested at a revision.
+struct InlayHintCache {
+    revision: u64,
+    /// Covers the requested ranges, `None` for the whole file.
+    range: Option<ide::TextRange>,
+    /// With the types they refer to, resolved on first use.
+    hints: Vec<(inlay_hints::InlayHint, OnceCell<InlayHintTarget>)>,
+}
+
+/// The name and location of the type an inlay hint refers to.
+type InlayHintTarget = Option<(String, Location)>;
+
+/// The last completion list, kept around so that `resolve_completion` can
+/// compute documentation and import edits of single items on demand.
+///
+/// Monaco keeps filtering the list while the user types, so items are
+/// resolved at the position the list was computed at, like rust-analyzer
+/// does for `completionItem/resolve`.
+struct CompletionList {
+    position: FilePosition,
+    items: Vec<ide::CompletionItem>,
 }
 
+const LOCAL_CRATE_NAME: &str = "my_crate";
+
 pub fn create_source_root(name: &str, f: FileId) -> SourceRoot {
     let mut file_set = FileSet::default();
     file_set.insert(f, VfsPath::new_virtual_path(format!("/{}/src/lib.rs", name)));
     SourceRoot::new_library(file_set)
 }
 
-pub fn create_crate(crate_graph: &mut CrateGraph, f: FileId) -> CrateId {
+pub fn create_crate(crate_graph: &mut CrateGraph, name: &str, f: FileId) -> CrateId {
     let mut cfg = CfgOptions::default();
     cfg.insert_atom("unix".into());
     cfg.insert_key_value("target_arch".into(), "x86_64".into());
@@ -52,7 +100,7 @@ pub fn create_crate(crate_graph: &mut CrateGraph, f: FileId) -> CrateId {
     crate_graph.add_crate_root(
         f,
         Edition::Edition2018,
-        None,
+        Some(CrateDisplayName::from_canonical_name(name.to_string())),
         None,
         cfg,
         Default::default(),
@@ -74,7 +122,7 @@ pub fn from_single_file(
     let alloc_id = FileId(3);
 
     let mut file_set = FileSet::default();
-    file_set.insert(file_id, VfsPath::new_virtual_path("/my_crate/main.rs".to_string()));
+    file_set.insert(file_id, VfsPath::new_virtual_path(format!("/{}/main.rs", LOCAL_CRATE_NAME)));
     let source_root = SourceRoot::new_local(file_set);
 
     let mut change = Change::new();
@@ -85,10 +133,10 @@ pub fn from_single_file(
         create_source_root("alloc", alloc_id),
     ]);
     let mut crate_graph = CrateGraph::default();
-    let my_crate = create_crate(&mut crate_graph, file_id);
-    let std_crate = create_crate(&mut crate_graph, std_id);
-    let core_crate = create_crate(&mut crate_graph, core_id);
-    let alloc_crate = create_crate(&mut crate_graph, alloc_id);
+    let my_crate = create_crate(&mut crate_graph, LOCAL_CRATE_NAME, file_id);
+    let std_crate = create_crate(&mut crate_graph, "std", std_id);
+    let core_crate = create_crate(&mut crate_graph, "core", core_id);
+    let alloc_crate = create_crate(&mut crate_graph, "alloc", alloc_id);
     let core_dep = Dependency::new(CrateName::new("core").unwrap(), core_crate);
     let alloc_dep = Dependency::new(CrateName::new("alloc").unwrap(), alloc_crate);
     let std_dep = Dependency::new(CrateName::new("std").unwrap(), std_crate);
@@ -114,6 +162,192 @@ impl WorldState {
     fn analysis(&self) -> Analysis {
         self.host.analysis()
     }
+
+    fn file_uri(&self, file_id: FileId) -> String {
+        let db = self.host.raw_database();
+        let source_root = db.source_root(db.file_source_root(file_id));
+        let path = source_root.path_for_file(&file_id).unwrap();
+        format!("file://{}", path)
+    }
+
+    /// Resolves a path relative to the directory of its anchor file.
+    fn anchored_path_uri(&self, path: &AnchoredPathBuf) -> String {
+        let db = self.host.raw_database();
+        let source_root = db.source_root(db.file_source_root(path.anchor));
+        let anchor = source_root.path_for_file(&path.anchor).unwrap();
+        let dst = anchor.parent().and_then(|dir| dir.join(&path.path)).unwrap();
+        format!("file://{}", dst)
+    }
+
+    fn location(&self, nav: &NavigationTarget) -> Location {
+        let line_index = self.analysis().file_line_index(nav.file_id).unwrap();
+        Location {
+            uri: self.file_uri(nav.file_id),
+            range: to_proto::text_range(nav.focus_range.unwrap_or(nav.full_range), &line_index),
+        }
+    }
+
+    /// Links from `nav_info.range` in the main file to the targets, which
+    /// may be in any file.
+    fn location_links(&self, nav_info: ide::RangeInfo<Vec<NavigationTarget>>) -> Vec<LocationLink> {
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+        let origin = to_proto::text_range(nav_info.range, &line_index);
+        nav_info
+            .info
+            .iter()
+            .map(|nav| {
+                let line_index = self.analysis().file_line_index(nav.file_id).unwrap();
+                to_proto::location_link(
+                    origin,
+                    self.file_uri(nav.file_id),
+                    nav.full_range,
+                    nav.focus_or_full_range(),
+                    &line_index,
+                )
+            })
+            .collect()
+    }
+
+    /// Converts a source change, applying the text edits before any file is
+    /// created or moved so that they still refer to the original paths.
+    fn workspace_edit(&self, change: ide::SourceChange) -> WorkspaceEdit {
+        let mut edits = Vec::new();
+        for (file_id, edit) in change.source_file_edits {
+            let line_index = self.analysis().file_line_index(file_id).unwrap();
+            let resource = self.file_uri(file_id);
+            edits.extend(to_proto::text_edits(edit, &line_index).into_iter().map(|edit| {
+                WorkspaceEditItem::Text(WorkspaceTextEdit { resource: resource.clone(), edit })
+            }));
+        }
+        for op in change.file_system_edits {
+            match op {
+                FileSystemEdit::CreateFile { dst, initial_contents } => {
+                    let uri = self.anchored_path_uri(&dst);
+                    edits.push(WorkspaceEditItem::File(WorkspaceFileEdit {
+                        oldUri: None,
+                        newUri: Some(uri.clone()),
+                        options: WorkspaceFileEditOptions {
+                            overwrite: false,
+                            ignoreIfExists: true,
+                        },
+                    }));
+                    if !initial_contents.is_empty() {
+                        let start = Range {
+                            startLineNumber: 1,
+                            startColumn: 1,
+                            endLineNumber: 1,
+                            endColumn: 1,
+                        };
+                        edits.push(WorkspaceEditItem::Text(WorkspaceTextEdit {
+                            resource: uri,
+                            edit: TextEdit { range: start, text: initial_contents },
+                        }));
+                    }
+                }
+                FileSystemEdit::MoveFile { src, dst } => {
+                    edits.push(WorkspaceEditItem::File(WorkspaceFileEdit {
+                        oldUri: Some(self.file_uri(src)),
+                        newUri: Some(self.anchored_path_uri(&dst)),
+                        options: WorkspaceFileEditOptions {
+                            overwrite: false,
+                            ignoreIfExists: false,
+                        },
+                    }));
+                }
+            }
+        }
+        WorkspaceEdit { edits }
+    }
+
+    fn file_diagnostics(&self, file_id: FileId) -> Vec<Diagnostic> {
+        let line_index = self.analysis().file_line_index(file_id).unwrap();
+        let config = self.config.diagnostics();
+
+        self.analysis()
+            .diagnostics(&config, ide::AssistResolveStrategy::All, file_id)
+            .unwrap()
+            .into_iter()
+            .map(|d| {
+                let Range { startLineNumber, startColumn, endLineNumber, endColumn } =
+                    to_proto::text_range(d.range, &line_index);
+                let fixes = d
+                    .fixes
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|assist| {
+                        Some(CodeAction {
+                            title: assist.label.to_string(),
+                            kind: "quickfix".into(),
+                            edit: self.workspace_edit(assist.source_change?),
+                        })
+                    })
+                    .collect();
+                Diagnostic {
+                    message: d.message,
+                    code: to_proto::diagnostic_code(d.code.as_str()),
+                    severity: match self.config.diagnostics.severity.get(d.code.as_str()) {
+                        Some(&severity) => to_proto::severity_override(severity),
+                        None => to_proto::severity(d.severity),
+                    },
+                    tags: if d.unused { vec![MarkerTag::Unnecessary] } else { Vec::new() },
+                    startLineNumber,
+                    startColumn,
+                    endLineNumber,
+                    endColumn,
+                    fixes,
+                    experimental: d.experimental,
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the source roots of all crates.
+    fn source_roots(&self) -> Vec<Arc<SourceRoot>> {
+        let db = self.host.raw_database();
+        let crate_graph = db.crate_graph();
+        let mut source_roots = Vec::new();
+        for krate in crate_graph.iter() {
+            let source_root_id = db.file_source_root(crate_graph[krate].root_file_id);
+            if !source_roots.contains(&source_root_id) {
+                source_roots.push(source_root_id);
+            }
+        }
+        source_roots.into_iter().map(|id| db.source_root(id)).collect()
+    }
+
+    /// Returns all files in the source roots of the local crates.
+    fn local_files(&self) -> Vec<FileId> {
+        self.source_roots()
+            .into_iter()
+            .filter(|source_root| !source_root.is_library)
+            .flat_map(|source_root| source_root.iter().collect::<Vec<_>>())
+            .collect()
+    }
+
+    /// Finds the type an inlay hint refers to, so that it can be made clickable.
+    fn inlay_hint_target(&self, hint: &inlay_hints::InlayHint) -> InlayHintTarget {
+        use inlay_hints::InlayKind;
+
+        let offset = match hint.kind {
+            InlayKind::Parameter | InlayKind::Lifetime | InlayKind::GenericParamList => {
+                return None
+            }
+            InlayKind::Type => hint.range.start(),
+            // The start of a chain is its innermost receiver, look at the
+            // closing token of the whole expression instead.
+            InlayKind::Chaining => hint.range.end() - TextSize::from(1),
+            // The type of the block body is the return type.
+            InlayKind::ClosureReturnType => {
+                let file = self.analysis().parse(self.file_id).ok()?;
+                let closure =
+                    find_node_at_offset::<ast::ClosureExpr>(file.syntax(), hint.range.start())?;
+                closure.body()?.syntax().text_range().end() - TextSize::from(1)
+            }
+        };
+        let position = FilePosition { file_id: self.file_id, offset };
+        let nav = self.analysis().goto_type_definition(position).ok()??.info.into_iter().next()?;
+        Some((nav.name.to_string(), self.location(&nav)))
+    }
 }
 
 #[wasm_bindgen]
@@ -122,21 +356,84 @@ impl WorldState {
     pub fn new() -> Self {
         let (host, file_id) =
             from_single_file("".to_owned(), "".to_owned(), "".to_owned(), "".to_owned());
-        Self { host, file_id }
+        Self {
+            host,
+            file_id,
+            config: Default::default(),
+            raw_config: None,
+            revision: 0,
+            completions: None,
+            inlay_hints: None,
+            timings: Default::default(),
+        }
     }
 
     pub fn init(&mut self, code: String, fake_std: String, fake_core: String, fake_alloc: String) {
+        let _timer = self.timings.start("init");
         let (host, file_id) = from_single_file(code, fake_std, fake_core, fake_alloc);
         self.host = host;
         self.file_id = file_id;
+        self.revision += 1;
+    }
+
+    /// Sets the log level filter, e.g. `warn,ra_ap_hir_def=info`, see
+    /// `logger` for the syntax.
+    pub fn set_log_level(&self, filter: &str) -> Result<(), JsValue> {
+        logger::set_filter(filter).map_err(|err| JsValue::from_str(&err))
+    }
+
+    pub fn set_config(&mut self, config: JsValue) -> Result<(), JsValue> {
+        let _timer = self.timings.start("set_config");
+        let raw_config = js_sys::JSON::stringify(&config)?;
+        self.config = serde_wasm_bindgen::from_value(config)?;
+        self.raw_config = Some(raw_config.into());
+        self.inlay_hints = None;
+        Ok(())
+    }
+
+    /// Serializes the files, crate graph and config, so that the state can
+    /// be restored with `import_state` instead of running `init` again.
+    pub fn export_state(&self) -> Vec<u8> {
+        let _timer = self.timings.start("export_state");
+        let snapshot = serde_wasm_bindgen::to_value(&snapshot::export(self)).unwrap();
+        String::from(js_sys::JSON::stringify(&snapshot).unwrap()).into_bytes()
+    }
+
+    /// The format version of `export_state`, states of other versions are
+    /// rejected by `import_state`.
+    pub fn state_version(&self) -> u32 {
+        snapshot::VERSION
+    }
+
+    pub fn import_state(&mut self, bytes: &[u8]) -> Result<(), JsValue> {
+        let _timer = self.timings.start("import_state");
+        let json = std::str::from_utf8(bytes).map_err(|err| JsValue::from_str(&err.to_string()))?;
+        let snapshot: snapshot::Snapshot =
+            serde_wasm_bindgen::from_value(js_sys::JSON::parse(json)?)?;
+        let config = match &snapshot.config {
+            Some(config) => serde_wasm_bindgen::from_value(js_sys::JSON::parse(config)?)?,
+            None => Default::default(),
+        };
+        let raw_config = snapshot.config.clone();
+        let (host, file_id) = snapshot::restore(snapshot).map_err(|err| JsValue::from_str(&err))?;
+
+        self.host = host;
+        self.file_id = file_id;
+        self.config = config;
+        self.raw_config = raw_config;
+        self.revision += 1;
+        self.completions = None;
+        self.inlay_hints = None;
+        Ok(())
     }
 
     pub fn update(&mut self, code: String) -> JsValue {
-        log::warn!("update");
-        let file_id = FileId(0);
+        let _timer = self.timings.start("update");
+        let file_id = self.file_id;
         let mut change = Change::new();
         change.change_file(file_id, Some(Arc::new(code)));
         self.host.apply_change(change);
+        self.revision += 1;
 
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
@@ -151,91 +448,166 @@ impl WorldState {
             })
             .collect();
 
-        let config = DiagnosticsConfig::default();
+        let diagnostics = self.file_diagnostics(file_id);
 
-        let diagnostics: Vec<_> = self
-            .analysis()
-            .diagnostics(&config, ide::AssistResolveStrategy::All, file_id)
-            .unwrap()
+        serde_wasm_bindgen::to_value(&UpdateResult { diagnostics, highlights }).unwrap()
+    }
+
+    /// Returns the diagnostics of all files of the local crates. Files whose
+    /// diagnostics are unchanged since the result id passed for their URI in
+    /// `previous_result_ids` are reported as `unchanged`, without diagnostics.
+    pub fn workspace_diagnostics(&self, previous_result_ids: JsValue) -> JsValue {
+        let _timer = self.timings.start("workspace_diagnostics");
+        let previous_result_ids: BTreeMap<String, String> =
+            match serde_wasm_bindgen::from_value::<Option<_>>(previous_result_ids) {
+                Ok(it) => it.unwrap_or_default(),
+                Err(_) => return JsValue::NULL,
+            };
+
+        let items: Vec<_> = self
+            .local_files()
             .into_iter()
-            .map(|d| {
-                let Range { startLineNumber, startColumn, endLineNumber, endColumn } =
-                    to_proto::text_range(d.range, &line_index);
-                Diagnostic {
-                    message: d.message,
-                    severity: to_proto::severity(d.severity),
-                    startLineNumber,
-                    startColumn,
-                    endLineNumber,
-                    endColumn,
+            .map(|file_id| {
+                let uri = self.file_uri(file_id);
+                let diagnostics = self.file_diagnostics(file_id);
+                let mut hasher = DefaultHasher::new();
+                diagnostics.hash(&mut hasher);
+                let result_id = format!("{:016x}", hasher.finish());
+
+                if previous_result_ids.get(&uri) == Some(&result_id) {
+                    FileDiagnosticReport {
+                        uri,
+                        kind: DiagnosticReportKind::Unchanged,
+                        resultId: result_id,
+                        diagnostics: None,
+                    }
+                } else {
+                    FileDiagnosticReport {
+                        uri,
+                        kind: DiagnosticReportKind::Full,
+                        resultId: result_id,
+                        diagnostics: Some(diagnostics),
+                    }
                 }
             })
             .collect();
 
-        serde_wasm_bindgen::to_value(&UpdateResult { diagnostics, highlights }).unwrap()
+        serde_wasm_bindgen::to_value(&WorkspaceDiagnosticReport { items }).unwrap()
     }
 
-    pub fn inlay_hints(&self) -> JsValue {
+    /// Returns the inlay hints intersecting `range`, or those of the whole
+    /// file if `range` is `null`.
+    pub fn inlay_hints(&mut self, range: JsValue) -> JsValue {
+        let _timer = self.timings.start("inlay_hints");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
-        let results: Vec<_> = self
-            .analysis()
-            .inlay_hints(
-                &InlayHintsConfig {
-                    type_hints: true,
-                    parameter_hints: true,
-                    chaining_hints: true,
-                    max_length: Some(25),
-                },
-                self.file_id,
-            )
-            .unwrap()
-            .into_iter()
-            .map(|ih| InlayHint {
-                label: Some(ih.label.to_string()),
-                hint_type: match ih.kind {
-                    InlayKind::TypeHint | InlayKind::ChainingHint => InlayHintType::Type,
-                    InlayKind::ParameterHint => InlayHintType::Parameter,
-                },
-                range: to_proto::text_range(ih.range, &line_index),
+        let range = match serde_wasm_bindgen::from_value::<Option<Range>>(range) {
+            Ok(range) => range.map(|range| {
+                file_range(
+                    range.startLineNumber,
+                    range.startColumn,
+                    range.endLineNumber,
+                    range.endColumn,
+                    &line_index,
+                    self.file_id,
+                )
+                .range
+            }),
+            Err(_) => return JsValue::NULL,
+        };
+
+        // Scrolling back to hints computed before doesn't recompute them.
+        let cached_range = match &self.inlay_hints {
+            Some(cache) if cache.revision == self.revision => Some(cache.range),
+            _ => None,
+        };
+        let covered = match (cached_range, range) {
+            (Some(None), _) => true,
+            (Some(Some(cached)), Some(range)) => cached.contains_range(range),
+            _ => false,
+        };
+        if !covered {
+            let range = match (cached_range, range) {
+                (Some(Some(cached)), Some(range)) => Some(cached.cover(range)),
+                _ => range,
+            };
+            let hints = inlay_hints::inlay_hints(&self.host, self.file_id, range, &self.config);
+            let hints = hints.into_iter().map(|it| (it, OnceCell::new())).collect();
+            self.inlay_hints = Some(InlayHintCache { revision: self.revision, range, hints });
+        }
+        let hints = &self.inlay_hints.as_ref().unwrap().hints;
+
+        let render_colons = self.config.inlay_hints.render_colons;
+        let results: Vec<_> = hints
+            .iter()
+            .filter(|(ih, _)| range.map_or(true, |range| ih.range.intersect(range).is_some()))
+            .map(|(ih, target)| {
+                let target = target.get_or_init(|| self.inlay_hint_target(ih)).clone();
+                to_proto::inlay_hint(ih, target, render_colons, &line_index)
             })
             .collect();
         serde_wasm_bindgen::to_value(&results).unwrap()
     }
 
-    pub fn completions(&self, line_number: u32, column: u32) -> JsValue {
-        const COMPLETION_CONFIG: CompletionConfig = CompletionConfig {
-            enable_postfix_completions: true,
-            enable_imports_on_the_fly: true,
-            enable_self_on_the_fly: true,
-            add_call_parenthesis: true,
-            add_call_argument_snippets: true,
-            snippet_cap: SnippetCap::new(true),
-            insert_use: InsertUseConfig {
-                granularity: ImportGranularity::Module,
-                enforce_granularity: false,
-                prefix_kind: PrefixKind::Plain,
-                group: true,
-                skip_glob_imports: false,
-            },
-            snippets: Vec::new(),
-        };
-
-        log::warn!("completions");
+    pub fn completions(&mut self, line_number: u32, column: u32) -> JsValue {
+        let _timer = self.timings.start("completions");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
-        let res = match self.analysis().completions(&COMPLETION_CONFIG, pos).unwrap() {
+        let res = match self.analysis().completions(&self.config.completion(), pos).unwrap() {
             Some(items) => items,
             None => return JsValue::NULL,
         };
 
-        let items: Vec<_> =
-            res.into_iter().map(|item| to_proto::completion_item(item, &line_index)).collect();
+        let file_text = self.analysis().file_text(self.file_id).unwrap();
+        let items = to_proto::completion_items(&res, &file_text, &line_index);
+        self.completions = Some(CompletionList { position: pos, items: res });
         serde_wasm_bindgen::to_value(&items).unwrap()
     }
 
+    pub fn resolve_completion(&self, item_id: usize) -> JsValue {
+        let _timer = self.timings.start("resolve_completion");
+        let (position, item) = match &self.completions {
+            Some(list) => match list.items.get(item_id) {
+                Some(item) => (list.position, item),
+                None => return JsValue::NULL,
+            },
+            None => return JsValue::NULL,
+        };
+        let line_index = self.analysis().file_line_index(position.file_id).unwrap();
+
+        let documentation = item.documentation().map(|doc| {
+            let mut doc = to_proto::markdown_string(doc.as_str());
+            doc.value = doc_links::rewrite_links(self, &doc.value);
+            doc.isTrusted = doc_links::trust();
+            doc
+        });
+
+        let mut additional_text_edits = Vec::new();
+        if let Some(import_edit) = item.import_to_add() {
+            let import_path = &import_edit.import.import_path;
+            if let Some(imported_name) = import_path.segments().last() {
+                let edits = self
+                    .analysis()
+                    .resolve_completion_edits(
+                        &self.config.completion(),
+                        position,
+                        &import_path.to_string(),
+                        imported_name.to_string(),
+                    )
+                    .unwrap();
+                additional_text_edits.extend(
+                    edits.into_iter().flat_map(|edit| to_proto::text_edits(edit, &line_index)),
+                );
+            }
+        }
+
+        let resolved =
+            ResolvedCompletionItem { documentation, additionalTextEdits: additional_text_edits };
+        serde_wasm_bindgen::to_value(&resolved).unwrap()
+    }
+
     pub fn hover(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("hover");
+        let _timer = self.timings.start("hover");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let range = file_range(line_number, column, line_number, column, &line_index, self.file_id);
@@ -254,17 +626,33 @@ impl WorldState {
             _ => return JsValue::NULL,
         };
 
-        let value = info.info.markup.to_string();
+        let value = doc_links::rewrite_links(self, &info.info.markup.to_string());
         let hover = Hover {
-            contents: vec![MarkdownString { value }],
+            contents: vec![MarkdownString { value, isTrusted: doc_links::trust() }],
             range: to_proto::text_range(info.range, &line_index),
         };
 
         serde_wasm_bindgen::to_value(&hover).unwrap()
     }
 
+    pub fn external_docs(&self, line_number: u32, column: u32) -> JsValue {
+        let _timer = self.timings.start("external_docs");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+
+        let pos = file_position(line_number, column, &line_index, self.file_id);
+        let url = match self.analysis().external_docs(pos) {
+            Ok(Some(link)) => doc_links::external_url(&link),
+            _ => None,
+        };
+
+        match url {
+            Some(url) => serde_wasm_bindgen::to_value(&url).unwrap(),
+            None => JsValue::NULL,
+        }
+    }
+
     pub fn code_lenses(&self) -> JsValue {
-        log::warn!("code_lenses");
+        let _timer = self.timings.start("code_lenses");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let results: Vec<_> = self
@@ -313,12 +701,26 @@ impl WorldState {
         serde_wasm_bindgen::to_value(&results).unwrap()
     }
 
-    pub fn references(&self, line_number: u32, column: u32, include_declaration: bool) -> JsValue {
-        log::warn!("references");
+    pub fn references(
+        &self,
+        line_number: u32,
+        column: u32,
+        include_declaration: bool,
+        scope: JsValue,
+    ) -> JsValue {
+        let _timer = self.timings.start("references");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
-        let search_scope = Some(SearchScope::single_file(self.file_id));
+        let scope = match serde_wasm_bindgen::from_value(scope) {
+            Ok(scope) => scope,
+            Err(_) => return JsValue::NULL,
+        };
+        let search_scope = match scope {
+            ReferenceScope::File => Some(SearchScope::single_file(self.file_id)),
+            ReferenceScope::Crates => Some(SearchScope::files(&self.local_files())),
+            ReferenceScope::All => None,
+        };
         let ref_results = match self.analysis().find_all_refs(pos, search_scope) {
             Ok(Some(info)) => info,
             _ => return JsValue::NULL,
@@ -328,23 +730,34 @@ impl WorldState {
         for ref_result in ref_results {
             if include_declaration {
                 if let Some(r) = ref_result.declaration {
-                    let r = r.nav.focus_range.unwrap_or(r.nav.full_range);
-                    res.push(Highlight { tag: None, range: to_proto::text_range(r, &line_index) });
+                    let Location { uri, range } = self.location(&r.nav);
+                    res.push(Reference {
+                        uri,
+                        range,
+                        category: Some(ReferenceCategory::Declaration),
+                    });
                 }
             }
-            ref_result.references.iter().for_each(|(_id, ranges)| {
-                // FIXME: handle multiple files
-                for (r, _) in ranges {
-                    res.push(Highlight { tag: None, range: to_proto::text_range(*r, &line_index) });
+            for (file_id, ranges) in ref_result.references {
+                let line_index = self.analysis().file_line_index(file_id).unwrap();
+                let uri = self.file_uri(file_id);
+                for (r, category) in ranges {
+                    res.push(Reference {
+                        uri: uri.clone(),
+                        range: to_proto::text_range(r, &line_index),
+                        category: category.map(to_proto::reference_category),
+                    });
                 }
-            });
+            }
         }
+        // The references are collected from a hash map, keep each file together.
+        res.sort_by(|a, b| a.uri.cmp(&b.uri));
 
         serde_wasm_bindgen::to_value(&res).unwrap()
     }
 
     pub fn prepare_rename(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("prepare_rename");
+        let _timer = self.timings.start("prepare_rename");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
@@ -361,7 +774,7 @@ impl WorldState {
     }
 
     pub fn rename(&self, line_number: u32, column: u32, new_name: &str) -> JsValue {
-        log::warn!("rename");
+        let _timer = self.timings.start("rename");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
@@ -370,18 +783,51 @@ impl WorldState {
             Err(_) => return JsValue::NULL,
         };
 
-        let result: Vec<_> = change
-            .source_file_edits
-            .iter()
-            .flat_map(|(_, edit)| edit.iter())
-            .map(|atom: &Indel| to_proto::text_edit(atom, &line_index))
-            .collect();
+        let result = self.workspace_edit(change);
+        serde_wasm_bindgen::to_value(&result).unwrap()
+    }
 
+    /// Runs a structural search and replace query such as
+    /// `foo($a, $b) ==>> bar($b, $a)` over the user crates. With `parse_only`
+    /// the query is only checked for errors.
+    pub fn structural_search_replace(&self, query: &str, parse_only: bool) -> JsValue {
+        let _timer = self.timings.start("structural_search_replace");
+        // Paths in the query are resolved relative to the crate root.
+        let resolve_context = FilePosition { file_id: self.file_id, offset: TextSize::from(0) };
+        let change = match self.analysis().structural_search_replace(
+            query,
+            parse_only,
+            resolve_context,
+            Vec::new(),
+        ) {
+            Ok(it) => it,
+            Err(_) => return JsValue::NULL,
+        };
+
+        let result = match change {
+            Ok(change) => {
+                let mut matches = Vec::new();
+                for (file_id, edit) in change.source_file_edits.iter() {
+                    let line_index = self.analysis().file_line_index(*file_id).unwrap();
+                    let uri = self.file_uri(*file_id);
+                    matches.extend(edit.iter().map(|indel| Location {
+                        uri: uri.clone(),
+                        range: to_proto::text_range(indel.delete, &line_index),
+                    }));
+                }
+                SsrResult { edit: self.workspace_edit(change), matches, error: None }
+            }
+            Err(err) => SsrResult {
+                edit: WorkspaceEdit { edits: Vec::new() },
+                matches: Vec::new(),
+                error: Some(to_proto::ssr_error(query, err)),
+            },
+        };
         serde_wasm_bindgen::to_value(&result).unwrap()
     }
 
     pub fn signature_help(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("signature_help");
+        let _timer = self.timings.start("signature_help");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
@@ -391,32 +837,86 @@ impl WorldState {
         };
 
         let active_parameter = call_info.active_parameter;
-        let sig_info = to_proto::signature_information(call_info);
+        let mut signatures = vec![to_proto::signature_information(call_info)];
+
+        // Calls of trait methods also list the implementations for the type
+        // of `Self`, which may be several, e.g. for `From::from`.
+        if let Some((name, is_method_call)) = navigation::callee(self, pos) {
+            if let Ok(Some(defs)) = self.analysis().goto_definition(name) {
+                let impls = navigation::self_type_impls(self, name, &defs.info);
+                signatures.extend(impls.iter().filter_map(|nav| {
+                    let file = self.analysis().parse(nav.file_id).ok()?;
+                    let offset = nav.focus_or_full_range().start();
+                    let func = find_node_at_offset::<ast::Fn>(file.syntax(), offset)?;
+                    let doc = nav.docs.as_ref().map(|it| it.as_str());
+                    Some(to_proto::fn_signature_information(&func, is_method_call, doc))
+                }));
+            }
+        }
 
+        // A single implementation is the one being called.
         let result = SignatureHelp {
-            signatures: [sig_info],
-            activeSignature: 0,
+            activeSignature: if signatures.len() == 2 { 1 } else { 0 },
+            signatures,
             activeParameter: active_parameter,
         };
         serde_wasm_bindgen::to_value(&result).unwrap()
     }
 
-    pub fn definition(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("definition");
+    /// With `include_implementations`, definitions of trait items are
+    /// followed by their implementations for the type of `Self` at the
+    /// cursor, e.g. the receiver of a method call.
+    pub fn definition(
+        &self,
+        line_number: u32,
+        column: u32,
+        include_implementations: bool,
+    ) -> JsValue {
+        let _timer = self.timings.start("definition");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
-        let nav_info = match self.analysis().goto_definition(pos) {
+        let mut nav_info = match self.analysis().goto_definition(pos) {
             Ok(Some(nav_info)) => nav_info,
             _ => return JsValue::NULL,
         };
 
-        let res = to_proto::location_links(nav_info, &line_index);
+        if include_implementations {
+            let impls = navigation::self_type_impls(self, pos, &nav_info.info);
+            nav_info.info.extend(impls);
+        }
+
+        let res = self.location_links(nav_info);
+        serde_wasm_bindgen::to_value(&res).unwrap()
+    }
+
+    /// Goes to the trait item declaring an item of a trait impl, or to the
+    /// `use` item declaring a path, see `navigation::declaration`.
+    pub fn declaration(&self, line_number: u32, column: u32) -> JsValue {
+        let _timer = self.timings.start("declaration");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+
+        let pos = file_position(line_number, column, &line_index, self.file_id);
+        let declarations = match navigation::declaration(self, pos) {
+            Some(it) => it,
+            None => return JsValue::NULL,
+        };
+
+        let origin = to_proto::text_range(declarations.range, &line_index);
+        let res: Vec<_> = declarations
+            .info
+            .into_iter()
+            .map(|it| {
+                let line_index = self.analysis().file_line_index(it.file_id).unwrap();
+                let uri = self.file_uri(it.file_id);
+                to_proto::location_link(origin, uri, it.full_range, it.focus_range, &line_index)
+            })
+            .collect();
         serde_wasm_bindgen::to_value(&res).unwrap()
     }
 
     pub fn type_definition(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("type_definition");
+        let _timer = self.timings.start("type_definition");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
@@ -425,12 +925,12 @@ impl WorldState {
             _ => return JsValue::NULL,
         };
 
-        let res = to_proto::location_links(nav_info, &line_index);
+        let res = self.location_links(nav_info);
         serde_wasm_bindgen::to_value(&res).unwrap()
     }
 
     pub fn document_symbols(&self) -> JsValue {
-        log::warn!("document_symbols");
+        let _timer = self.timings.start("document_symbols");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let struct_nodes = match self.analysis().file_structure(self.file_id) {
@@ -470,7 +970,7 @@ impl WorldState {
     }
 
     pub fn type_formatting(&self, line_number: u32, column: u32, ch: char) -> JsValue {
-        log::warn!("type_formatting");
+        let _timer = self.timings.start("type_formatting");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let mut pos = file_position(line_number, column, &line_index, self.file_id);
@@ -487,8 +987,215 @@ impl WorldState {
         serde_wasm_bindgen::to_value(&change).unwrap()
     }
 
+    pub fn on_enter(&self, line_number: u32, column: u32) -> JsValue {
+        let _timer = self.timings.start("on_enter");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+
+        let pos = file_position(line_number, column, &line_index, self.file_id);
+        let edit = match self.analysis().on_enter(pos) {
+            Ok(Some(it)) => it,
+            _ => return JsValue::NULL,
+        };
+
+        // The edit is a snippet, with `$0` marking the new cursor position.
+        let change: Vec<TextEdit> = to_proto::text_edits(edit, &line_index);
+        serde_wasm_bindgen::to_value(&change).unwrap()
+    }
+
+    pub fn join_lines(&self, ranges: JsValue) -> JsValue {
+        let _timer = self.timings.start("join_lines");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+        let ranges: Vec<Range> = match serde_wasm_bindgen::from_value(ranges) {
+            Ok(it) => it,
+            Err(_) => return JsValue::NULL,
+        };
+
+        let config = self.config.join_lines();
+        let mut edit = ide::TextEdit::default();
+        for range in ranges {
+            let frange = file_range(
+                range.startLineNumber,
+                range.startColumn,
+                range.endLineNumber,
+                range.endColumn,
+                &line_index,
+                self.file_id,
+            );
+            // Edits overlapping those of a previous range are dropped.
+            let _ = edit.union(self.analysis().join_lines(&config, frange).unwrap());
+        }
+
+        let change: Vec<TextEdit> = to_proto::text_edits(edit, &line_index);
+        serde_wasm_bindgen::to_value(&change).unwrap()
+    }
+
+    /// Returns the position of the brace matching the one at each of
+    /// `positions`, or the position itself if there is none.
+    pub fn matching_brace(&self, positions: JsValue) -> JsValue {
+        let _timer = self.timings.start("matching_brace");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+        let positions: Vec<Position> = match serde_wasm_bindgen::from_value(positions) {
+            Ok(it) => it,
+            Err(_) => return JsValue::NULL,
+        };
+
+        let res: Vec<_> = positions
+            .into_iter()
+            .map(|position| {
+                let pos =
+                    file_position(position.lineNumber, position.column, &line_index, self.file_id);
+                match self.analysis().matching_brace(pos) {
+                    Ok(Some(offset)) => to_proto::position(offset, &line_index),
+                    _ => position,
+                }
+            })
+            .collect();
+        serde_wasm_bindgen::to_value(&res).unwrap()
+    }
+
+    /// Moves the item (function, match arm, field, ...) at `range` past its
+    /// neighbour. The returned edit is a snippet with `$0` at the new cursor.
+    pub fn move_item(&self, range: JsValue, direction: JsValue) -> JsValue {
+        let _timer = self.timings.start("move_item");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+        let (range, direction) = match (
+            serde_wasm_bindgen::from_value::<Range>(range),
+            serde_wasm_bindgen::from_value::<MoveDirection>(direction),
+        ) {
+            (Ok(range), Ok(direction)) => (range, direction),
+            _ => return JsValue::NULL,
+        };
+
+        let frange = file_range(
+            range.startLineNumber,
+            range.startColumn,
+            range.endLineNumber,
+            range.endColumn,
+            &line_index,
+            self.file_id,
+        );
+        let direction = match direction {
+            MoveDirection::Up => ide::Direction::Up,
+            MoveDirection::Down => ide::Direction::Down,
+        };
+        let edit = match self.analysis().move_item(frange, direction) {
+            Ok(Some(it)) => it,
+            _ => return JsValue::NULL,
+        };
+
+        let change: Vec<TextEdit> = to_proto::text_edits(edit, &line_index);
+        serde_wasm_bindgen::to_value(&change).unwrap()
+    }
+
+    pub fn parent_module(&self, line_number: u32, column: u32) -> JsValue {
+        let _timer = self.timings.start("parent_module");
+        let line_index = self.analysis().file_line_index(self.file_id).unwrap();
+
+        let pos = file_position(line_number, column, &line_index, self.file_id);
+        let navs = match self.analysis().parent_module(pos) {
+            Ok(it) => it,
+            _ => return JsValue::NULL,
+        };
+
+        let res: Vec<_> = navs.iter().map(|nav| self.location(nav)).collect();
+        serde_wasm_bindgen::to_value(&res).unwrap()
+    }
+
+    /// Returns the crate graph, leaving out the sysroot crates unless
+    /// `include_sysroot` is set.
+    pub fn view_crate_graph(&self, include_sysroot: bool) -> JsValue {
+        let _timer = self.timings.start("view_crate_graph");
+        let dot = match self.analysis().view_crate_graph(include_sysroot) {
+            Ok(Ok(it)) => it,
+            Ok(Err(err)) => {
+                log::warn!("failed to render the crate graph: {}", err);
+                return JsValue::NULL;
+            }
+            Err(_) => return JsValue::NULL,
+        };
+
+        let db = self.host.raw_database();
+        let crate_graph = db.crate_graph();
+        let is_library = |krate: CrateId| {
+            db.source_root(db.file_source_root(crate_graph[krate].root_file_id)).is_library
+        };
+        let crates: Vec<_> =
+            crate_graph.iter().filter(|&krate| include_sysroot || !is_library(krate)).collect();
+
+        let nodes = crates
+            .iter()
+            .map(|&krate| {
+                let data = &crate_graph[krate];
+                CrateNode {
+                    id: krate.0,
+                    name: data.display_name.as_ref().map(|it| it.to_string()),
+                    root: self.file_uri(data.root_file_id),
+                    isLibrary: is_library(krate),
+                }
+            })
+            .collect();
+        let edges = crates
+            .iter()
+            .flat_map(|&krate| {
+                crate_graph[krate]
+                    .dependencies
+                    .iter()
+                    .filter(|dep| crates.contains(&dep.crate_id))
+                    .map(move |dep| CrateEdge {
+                        from: krate.0,
+                        to: dep.crate_id.0,
+                        name: dep.name.to_string(),
+                    })
+            })
+            .collect();
+
+        serde_wasm_bindgen::to_value(&CrateGraphView { dot, nodes, edges }).unwrap()
+    }
+
+    /// Reports the state of the analysis: database statistics, the size of
+    /// the workspace and of the wasm memory, and how long each call took.
+    pub fn status(&self) -> JsValue {
+        let _timer = self.timings.start("status");
+        let analysis = match self.analysis().status(Some(self.file_id)) {
+            Ok(it) => it,
+            Err(_) => return JsValue::NULL,
+        };
+
+        let files = self.source_roots().iter().map(|source_root| source_root.iter().count()).sum();
+        let crates = self.host.raw_database().crate_graph().iter().count();
+        #[cfg(target_arch = "wasm32")]
+        let memoryBytes = core::arch::wasm32::memory_size::<0>() * 65536;
+        #[cfg(not(target_arch = "wasm32"))]
+        let memoryBytes = 0;
+
+        let status =
+            Status { analysis, files, crates, memoryBytes, queries: self.timings.report() };
+        serde_wasm_bindgen::to_value(&status).unwrap()
+    }
+
+    /// Starts recording a trace of the calls into `WorldState`, dropping the
+    /// previous one, or stops recording.
+    pub fn set_tracing(&self, enabled: bool) {
+        let _timer = self.timings.start("set_tracing");
+        self.timings.set_tracing(enabled);
+    }
+
+    /// Returns the trace as JSON in the Chrome trace event format, which the
+    /// performance panel of the browser can load. It has no spans for the
+    /// rayon pool: `ide_db` searches symbols for doc links there, with no
+    /// hooks to time it, so that time is part of spans such as `hover`.
+    pub fn export_trace(&self) -> JsValue {
+        let _timer = self.timings.start("export_trace");
+        let trace = Trace { traceEvents: self.timings.trace_events(), displayTimeUnit: "ms" };
+        let trace = serde_wasm_bindgen::to_value(&trace).unwrap();
+        match js_sys::JSON::stringify(&trace) {
+            Ok(json) => json.into(),
+            Err(_) => JsValue::NULL,
+        }
+    }
+
     pub fn folding_ranges(&self) -> JsValue {
-        log::warn!("folding_ranges");
+        let _timer = self.timings.start("folding_ranges");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
         if let Ok(folds) = self.analysis().folding_ranges(self.file_id) {
             let res: Vec<_> =
@@ -500,7 +1207,7 @@ impl WorldState {
     }
 
     pub fn goto_implementation(&self, line_number: u32, column: u32) -> JsValue {
-        log::warn!("goto_implementation");
+        let _timer = self.timings.start("goto_implementation");
         let line_index = self.analysis().file_line_index(self.file_id).unwrap();
 
         let pos = file_position(line_number, column, &line_index, self.file_id);
@@ -508,7 +1215,7 @@ impl WorldState {
             Ok(Some(it)) => it,
             _ => return JsValue::NULL,
         };
-        let res = to_proto::location_links(nav_info, &line_index);
+        let res = self.location_links(nav_info);
         serde_wasm_bindgen::to_value(&res).unwrap()
     }
 }
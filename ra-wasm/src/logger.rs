@@ -0,0 +1,192 @@
+//! A `log` implementation forwarding records to the browser console, or to a
+//! callback set with `set_log_callback`.
+//!
+//! Levels are filtered per module with directives in the style of
+//! `env_logger`, e.g. `warn,wasm_demo=debug,ra_ap_hir*=info`: the longest
+//! matching module prefix wins, a trailing `*` matches any crate name starting
+//! with the prefix. Without a default level only errors are logged. Calls into
+//! `WorldState` are logged at `debug` level.
+use std::{cell::RefCell, str::FromStr, sync::RwLock};
+
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use once_cell::sync::Lazy;
+use wasm_bindgen::prelude::*;
+
+static LOGGER: Lazy<Logger> = Lazy::new(|| Logger { filter: RwLock::new(Filter::default()) });
+
+thread_local! {
+    // Functions can't be shared between threads, records logged on the rayon
+    // threads always go to their console.
+    static CALLBACK: RefCell<Option<js_sys::Function>> = RefCell::new(None);
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(js_namespace = console, js_name = error)]
+    fn console_error(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = warn)]
+    fn console_warn(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = info)]
+    fn console_info(s: &str);
+    #[wasm_bindgen(js_namespace = console, js_name = debug)]
+    fn console_debug(s: &str);
+}
+
+/// Sends the records logged on this thread to `callback(level, target,
+/// message)` instead of the console, or back to the console if `None`.
+#[wasm_bindgen]
+pub fn set_log_callback(callback: Option<js_sys::Function>) {
+    CALLBACK.with(|it| *it.borrow_mut() = callback);
+}
+
+pub(crate) fn init() {
+    if log::set_logger(&*LOGGER).is_ok() {
+        log::set_max_level(LOGGER.filter.read().unwrap().max_level());
+    }
+}
+
+pub(crate) fn set_filter(spec: &str) -> Result<(), String> {
+    let filter: Filter = spec.parse()?;
+    log::set_max_level(filter.max_level());
+    *LOGGER.filter.write().unwrap() = filter;
+    Ok(())
+}
+
+struct Logger {
+    filter: RwLock<Filter>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.filter.read().unwrap().level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let message = record.args().to_string();
+        let handled = CALLBACK.with(|callback| match &*callback.borrow() {
+            Some(callback) => {
+                let level = JsValue::from_str(&record.level().to_string());
+                let target = JsValue::from_str(record.target());
+                let _ = callback.call3(&JsValue::NULL, &level, &target, &message.into());
+                true
+            }
+            None => false,
+        });
+        if handled {
+            return;
+        }
+
+        let message = format!("[{}] {}", record.target(), message);
+        match record.level() {
+            Level::Error => console_error(&message),
+            Level::Warn => console_warn(&message),
+            Level::Info => console_info(&message),
+            Level::Debug | Level::Trace => console_debug(&message),
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+struct Filter {
+    default: LevelFilter,
+    /// Sorted by decreasing length, so that the first match is the most specific.
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter { default: LevelFilter::Warn, modules: Vec::new() }
+    }
+}
+
+impl Filter {
+    fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .find(|(module, _)| match module.strip_suffix('*') {
+                Some(prefix) => target.starts_with(prefix),
+                None => {
+                    target.starts_with(module.as_str())
+                        && (target.len() == module.len()
+                            || target[module.len()..].starts_with("::"))
+                }
+            })
+            .map_or(self.default, |&(_, level)| level)
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(self.default, std::cmp::max)
+    }
+}
+
+impl FromStr for Filter {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut filter = Filter { default: LevelFilter::Error, modules: Vec::new() };
+        for directive in spec.split(',').map(str::trim).filter(|it| !it.is_empty()) {
+            let parse_level = |level: &str| {
+                level.trim().parse().map_err(|_| format!("invalid log level `{}`", level))
+            };
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    filter.modules.push((module.trim().to_string(), parse_level(level)?))
+                }
+                None => filter.default = parse_level(directive)?,
+            }
+        }
+        filter.modules.sort_by_key(|(module, _)| std::cmp::Reverse(module.len()));
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filter(spec: &str) -> Filter {
+        spec.parse().unwrap()
+    }
+
+    #[test]
+    fn default_level() {
+        assert_eq!(filter("").level_for("wasm_demo"), LevelFilter::Error);
+        assert_eq!(filter("warn").level_for("wasm_demo"), LevelFilter::Warn);
+        assert_eq!(filter(" info , ").level_for("ra_ap_ide"), LevelFilter::Info);
+    }
+
+    #[test]
+    fn module_prefixes() {
+        let filter = filter("wasm_demo=debug");
+        assert_eq!(filter.level_for("wasm_demo"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("wasm_demo::timings"), LevelFilter::Debug);
+        assert_eq!(filter.level_for("wasm_demo_x"), LevelFilter::Error);
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let filter = filter("warn,wasm_demo::timings=trace,wasm_demo=info");
+        assert_eq!(filter.level_for("wasm_demo::timings"), LevelFilter::Trace);
+        assert_eq!(filter.level_for("wasm_demo::logger"), LevelFilter::Info);
+        assert_eq!(filter.level_for("ra_ap_ide"), LevelFilter::Warn);
+        assert_eq!(filter.max_level(), LevelFilter::Trace);
+    }
+
+    #[test]
+    fn crate_name_wildcards() {
+        let filter = filter("ra_ap_hir*=info");
+        assert_eq!(filter.level_for("ra_ap_hir"), LevelFilter::Info);
+        assert_eq!(filter.level_for("ra_ap_hir_def::nameres"), LevelFilter::Info);
+        assert_eq!(filter.level_for("ra_ap_ide"), LevelFilter::Error);
+    }
+
+    #[test]
+    fn invalid_level() {
+        assert_eq!("wasm_demo=loud".parse::<Filter>().err().unwrap(), "invalid log level `loud`");
+        assert!("verbose".parse::<Filter>().is_err());
+    }
+}
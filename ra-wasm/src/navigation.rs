@@ -0,0 +1,351 @@
+//! Lookups combining several `Analysis` queries with the syntax around the
+//! cursor, for signature help and navigation.
+use ide::{FileId, FilePosition, NavigationTarget, RangeInfo, SymbolKind, TextRange};
+use syntax::{
+    algo::find_node_at_offset,
+    ast::{self, HasName, HasVisibility},
+    AstNode, SyntaxNode, TextSize,
+};
+
+use crate::WorldState;
+
+/// Finds the name of the function called by the innermost call around
+/// `position`, and whether it's called with the method call syntax.
+pub(crate) fn callee(world: &WorldState, position: FilePosition) -> Option<(FilePosition, bool)> {
+    let file = world.analysis().parse(position.file_id).ok()?;
+    let arg_list = find_node_at_offset::<ast::ArgList>(file.syntax(), position.offset)?;
+    let (name_ref, is_method_call) = match ast::Expr::cast(arg_list.syntax().parent()?)? {
+        ast::Expr::MethodCallExpr(call) => (call.name_ref()?, true),
+        ast::Expr::CallExpr(call) => match call.expr()? {
+            ast::Expr::PathExpr(path) => (path.path()?.segment()?.name_ref()?, false),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    let offset = name_ref.syntax().text_range().start();
+    Some((FilePosition { file_id: position.file_id, offset }, is_method_call))
+}
+
+/// The implementations of the trait items among `navs` for the `Self` type
+/// at `name`, see `self_type`. Without a known `Self` type there are none,
+/// rather than every implementation of the trait.
+pub(crate) fn self_type_impls(
+    world: &WorldState,
+    name: FilePosition,
+    navs: &[NavigationTarget],
+) -> Vec<NavigationTarget> {
+    let analysis = world.analysis();
+    let impl_blocks = match self_type(world, name)
+        .and_then(|it| analysis.goto_implementation(nav_position(&it)).ok()?)
+    {
+        Some(it) => it.info,
+        None => return Vec::new(),
+    };
+    navs.iter()
+        .filter_map(|nav| analysis.goto_implementation(nav_position(nav)).ok()?)
+        .flat_map(|it| it.info)
+        .filter(|item| {
+            impl_blocks.iter().any(|block| {
+                block.file_id == item.file_id && block.full_range.contains_range(item.full_range)
+            })
+        })
+        .collect()
+}
+
+/// The type of `Self` of the method call or associated item path at `name`,
+/// e.g. the type of `a` in `a.f()` or `A` in `A::f()`.
+fn self_type(world: &WorldState, name: FilePosition) -> Option<NavigationTarget> {
+    let analysis = world.analysis();
+    let file = analysis.parse(name.file_id).ok()?;
+    let name_ref = find_node_at_offset::<ast::NameRef>(file.syntax(), name.offset)?;
+    let parent = name_ref.syntax().parent()?;
+
+    if let Some(call) = ast::MethodCallExpr::cast(parent.clone()) {
+        let receiver = call.receiver()?.syntax().text_range();
+        let offset = receiver.end() - TextSize::from(1);
+        let navs = analysis.goto_type_definition(FilePosition { offset, ..name }).ok()??;
+        // The type itself comes before its type arguments.
+        return navs.info.into_iter().next();
+    }
+
+    let path = ast::PathSegment::cast(parent)?.syntax().parent().and_then(ast::Path::cast)?;
+    let qualifier = path.qualifier()?.segment()?.name_ref()?;
+    let offset = qualifier.syntax().text_range().start();
+    let navs = analysis.goto_definition(FilePosition { offset, ..name }).ok()??;
+    navs.info.into_iter().find(|nav| {
+        matches!(nav.kind, Some(SymbolKind::Struct | SymbolKind::Enum | SymbolKind::Union))
+    })
+}
+
+pub(crate) fn nav_position(nav: &NavigationTarget) -> FilePosition {
+    FilePosition { file_id: nav.file_id, offset: nav.focus_or_full_range().start() }
+}
+
+/// What a declaration link points at, a `NavigationTarget` without metadata.
+pub(crate) struct Declaration {
+    pub(crate) file_id: FileId,
+    pub(crate) full_range: TextRange,
+    pub(crate) focus_range: TextRange,
+}
+
+impl Declaration {
+    fn from_nav(nav: &NavigationTarget) -> Declaration {
+        let focus_range = nav.focus_or_full_range();
+        Declaration { file_id: nav.file_id, full_range: nav.full_range, focus_range }
+    }
+}
+
+/// Finds where the item at `position` is declared, as opposed to defined:
+/// items of trait impls are declared by the trait, and paths by the `use`
+/// bringing them into scope. For a qualified path that is a `pub use` in the
+/// module of the qualifier, i.e. a re-export. Other items are declared where
+/// they are defined.
+pub(crate) fn declaration(
+    world: &WorldState,
+    position: FilePosition,
+) -> Option<RangeInfo<Vec<Declaration>>> {
+    let analysis = world.analysis();
+    let file = analysis.parse(position.file_id).ok()?;
+
+    if let Some(name) = find_node_at_offset::<ast::Name>(file.syntax(), position.offset) {
+        if let Some(it) = trait_item(world, position.file_id, &name) {
+            return Some(RangeInfo::new(name.syntax().text_range(), vec![it]));
+        }
+    }
+
+    let definitions = analysis.goto_definition(position).ok()??;
+    if let Some(name_ref) = find_node_at_offset::<ast::NameRef>(file.syntax(), position.offset) {
+        if let Some(it) = import(world, position.file_id, &name_ref, &definitions.info) {
+            return Some(RangeInfo::new(definitions.range, vec![it]));
+        }
+    }
+    // Calls of trait methods may resolve to the implementation.
+    let declarations = definitions
+        .info
+        .iter()
+        .map(|nav| {
+            let impl_item = || {
+                let file = analysis.parse(nav.file_id).ok()?;
+                let offset = nav.focus_or_full_range().start();
+                let name = find_node_at_offset::<ast::Name>(file.syntax(), offset)?;
+                trait_item(world, nav.file_id, &name)
+            };
+            impl_item().unwrap_or_else(|| Declaration::from_nav(nav))
+        })
+        .collect();
+    Some(RangeInfo::new(definitions.range, declarations))
+}
+
+/// The item of the trait implemented by the item `name` of a trait impl.
+fn trait_item(world: &WorldState, file_id: FileId, name: &ast::Name) -> Option<Declaration> {
+    let analysis = world.analysis();
+    let item = name.syntax().parent().filter(|it| ast::AssocItem::can_cast(it.kind()))?;
+    let impl_ = item.parent()?.parent().and_then(ast::Impl::cast)?;
+    let trait_name = match impl_.trait_()? {
+        ast::Type::PathType(it) => it.path()?.segment()?.name_ref()?,
+        _ => return None,
+    };
+
+    let offset = trait_name.syntax().text_range().start();
+    let traits = analysis.goto_definition(FilePosition { file_id, offset }).ok()??;
+    let name = name.syntax().text().to_string();
+    traits.info.iter().find_map(|nav| {
+        let file = analysis.parse(nav.file_id).ok()?;
+        let offset = nav.focus_or_full_range().start();
+        let trait_ = find_node_at_offset::<ast::Trait>(file.syntax(), offset)?;
+        trait_.assoc_item_list()?.assoc_items().find_map(|trait_item| {
+            let trait_item_name = match &trait_item {
+                ast::AssocItem::Fn(it) => it.name(),
+                ast::AssocItem::Const(it) => it.name(),
+                ast::AssocItem::TypeAlias(it) => it.name(),
+                ast::AssocItem::MacroCall(_) => None,
+            }?;
+            let matches = trait_item.syntax().kind() == item.kind()
+                && trait_item_name.syntax().text().to_string() == name;
+            matches.then(|| Declaration {
+                file_id: nav.file_id,
+                full_range: trait_item.syntax().text_range(),
+                focus_range: trait_item_name.syntax().text_range(),
+            })
+        })
+    })
+}
+
+/// The `use` importing one of `definitions` under the name of the path at
+/// `name_ref`.
+fn import(
+    world: &WorldState,
+    file_id: FileId,
+    name_ref: &ast::NameRef,
+    definitions: &[NavigationTarget],
+) -> Option<Declaration> {
+    let analysis = world.analysis();
+    let path = name_ref.syntax().parent()?.parent().and_then(ast::Path::cast)?;
+    // The paths of `use` items are the declarations themselves.
+    if path.syntax().ancestors().any(|it| ast::Use::can_cast(it.kind())) {
+        return None;
+    }
+
+    let (scopes, only_pub) = match path.qualifier() {
+        Some(qualifier) => {
+            let offset = qualifier.segment()?.syntax().text_range().start();
+            let modules = analysis.goto_definition(FilePosition { file_id, offset }).ok()??;
+            let scopes = modules
+                .info
+                .iter()
+                .filter(|nav| nav.kind == Some(SymbolKind::Module))
+                .filter_map(|nav| module_scope(world, nav))
+                .collect();
+            (scopes, true)
+        }
+        None => {
+            let scopes = path
+                .syntax()
+                .ancestors()
+                .filter(|it| {
+                    ast::SourceFile::can_cast(it.kind())
+                        || ast::ItemList::can_cast(it.kind())
+                        || ast::BlockExpr::can_cast(it.kind())
+                })
+                .map(|it| (file_id, it))
+                .collect::<Vec<_>>();
+            (scopes, false)
+        }
+    };
+
+    let name = name_ref.syntax().text().to_string();
+    for (file_id, scope) in scopes {
+        // Items of blocks may be wrapped in a statement list.
+        let uses = scope
+            .children()
+            .flat_map(|it| std::iter::once(it.clone()).chain(it.children()))
+            .filter_map(ast::Use::cast)
+            .filter(|it| !only_pub || it.visibility().is_some());
+        for use_item in uses {
+            let mut trees = Vec::new();
+            if let Some(tree) = use_item.use_tree() {
+                use_trees_binding(tree, &name, &mut trees);
+            }
+            for tree in trees {
+                let offset = match tree.path().and_then(|it| it.segment()) {
+                    Some(segment) => segment.syntax().text_range().start(),
+                    None => continue,
+                };
+                let targets = match analysis.goto_definition(FilePosition { file_id, offset }) {
+                    Ok(Some(it)) => it.info,
+                    _ => continue,
+                };
+                let imports_definition = targets.iter().any(|target| {
+                    definitions.iter().any(|it| {
+                        it.file_id == target.file_id && it.full_range == target.full_range
+                    })
+                });
+                if imports_definition {
+                    return Some(Declaration {
+                        file_id,
+                        full_range: use_item.syntax().text_range(),
+                        focus_range: tree.syntax().text_range(),
+                    });
+                }
+            }
+        }
+    }
+    None
+}
+
+/// The items of the module `nav` points at: inline modules point at their
+/// `mod` item, others at their whole file.
+fn module_scope(world: &WorldState, nav: &NavigationTarget) -> Option<(FileId, SyntaxNode)> {
+    let file = world.analysis().parse(nav.file_id).ok()?;
+    if nav.focus_range.is_some() {
+        let offset = nav.focus_or_full_range().start();
+        let module = find_node_at_offset::<ast::Module>(file.syntax(), offset)?;
+        return Some((nav.file_id, module.item_list()?.syntax().clone()));
+    }
+    Some((nav.file_id, file.syntax().clone()))
+}
+
+/// Collects the leaves of `tree` which bind `name`, e.g. `a::b`,
+/// `a::{self}` or `c as b` for `b`. Glob imports bind no name in particular.
+fn use_trees_binding(tree: ast::UseTree, name: &str, acc: &mut Vec<ast::UseTree>) {
+    if let Some(list) = tree.use_tree_list() {
+        for it in list.use_trees() {
+            use_trees_binding(it, name, acc);
+        }
+        return;
+    }
+    let bound = match tree.rename() {
+        Some(rename) => rename.name().map(|it| it.syntax().text().to_string()),
+        None => {
+            let segment = tree.path().and_then(|it| it.segment());
+            match segment.map(|it| it.syntax().text().to_string()) {
+                // `a::{self}` binds the name of its parent, `a`.
+                Some(it) if it == "self" => tree
+                    .syntax()
+                    .parent()
+                    .and_then(|it| it.parent())
+                    .and_then(ast::UseTree::cast)
+                    .and_then(|it| it.path()?.segment())
+                    .map(|it| it.syntax().text().to_string()),
+                it => it,
+            }
+        }
+    };
+    if bound.as_deref() == Some(name) {
+        acc.push(tree);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks that the single declaration of the item at `$0` is `expect`.
+    fn check(fixture: &str, expect: &str) {
+        let offset = fixture.find("$0").unwrap();
+        let text = fixture.replacen("$0", "", 1);
+        let mut world = WorldState::new();
+        world.init(text.clone(), String::new(), String::new(), String::new());
+        let position =
+            FilePosition { file_id: world.file_id, offset: TextSize::from(offset as u32) };
+        let declarations = declaration(&world, position).unwrap().info;
+        assert_eq!(declarations.len(), 1);
+        assert_eq!(&text[declarations[0].full_range], expect);
+    }
+
+    #[test]
+    fn trait_impl_item() {
+        check(
+            "trait T { fn f(&self); } struct S; impl T for S { fn $0f(&self) {} }",
+            "fn f(&self);",
+        );
+    }
+
+    #[test]
+    fn use_self() {
+        check("mod a { pub fn f() {} } use a::{self}; fn g() { $0a::f(); }", "use a::{self};");
+    }
+
+    #[test]
+    fn use_rename() {
+        check("mod a { pub fn c() {} } use a::c as b; fn g() { $0b(); }", "use a::c as b;");
+    }
+
+    #[test]
+    fn use_in_block() {
+        check("mod a { pub fn f() {} } fn g() { use a::f; $0f(); }", "use a::f;");
+    }
+
+    #[test]
+    fn re_export() {
+        check(
+            "mod a { mod b { pub fn f() {} } pub use self::b::f; } fn g() { a::$0f(); }",
+            "pub use self::b::f;",
+        );
+    }
+
+    #[test]
+    fn definition_without_import() {
+        check("fn f() {} fn g() { $0f(); }", "fn f() {}");
+    }
+}
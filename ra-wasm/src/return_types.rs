@@ -7,7 +7,7 @@ pub struct Hover {
     pub contents: Vec<MarkdownString>,
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy)]
+#[derive(Serialize, Deserialize, Clone, Copy, Hash)]
 pub struct Range {
     pub startLineNumber: u32,
     pub startColumn: u32,
@@ -15,9 +15,16 @@ pub struct Range {
     pub endColumn: u32,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct MarkdownString {
     pub value: String,
+    /// Without it, links to commands don't run.
+    pub isTrusted: Option<MarkdownTrust>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MarkdownTrust {
+    pub enabledCommands: Vec<&'static str>,
 }
 
 #[derive(Serialize, Deserialize)]
@@ -26,7 +33,7 @@ pub struct CodeLensSymbol {
     pub command: Option<Command>,
 }
 
-#[derive(Serialize, Deserialize)]
+#[derive(Serialize, Deserialize, Clone)]
 pub struct Command {
     pub id: String,
     pub title: String,
@@ -39,21 +46,42 @@ pub struct Highlight {
     pub range: Range,
 }
 
-#[derive(Serialize_repr)]
+#[derive(Serialize, Deserialize, Clone, Copy)]
+pub struct Position {
+    pub lineNumber: u32,
+    pub column: u32,
+}
+
+#[derive(Serialize_repr, Clone, Copy)]
 #[repr(u8)]
 pub enum InlayHintType {
     Type = 1,
     Parameter = 2,
+    Chaining = 3,
+    ClosureReturnType = 4,
+    Lifetime = 5,
 }
 
 #[derive(Serialize)]
 pub struct InlayHint {
-    pub label: Option<String>,
+    /// The rendered text, including the `:` separator if enabled.
+    pub label: String,
+    /// `label` split up, with the type names linked to their definitions.
+    pub labelParts: Vec<InlayHintLabelPart>,
     pub hint_type: InlayHintType,
     pub range: Range,
+    pub position: Position,
+    pub paddingLeft: bool,
+    pub paddingRight: bool,
 }
 
 #[derive(Serialize)]
+pub struct InlayHintLabelPart {
+    pub value: String,
+    pub location: Option<Location>,
+}
+
+#[derive(Serialize, Clone, Hash)]
 pub struct TextEdit {
     pub range: Range,
     pub text: String,
@@ -65,18 +93,179 @@ pub struct UpdateResult {
     pub highlights: Vec<Highlight>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Hash)]
 pub struct Diagnostic {
     pub message: String,
+    pub code: DiagnosticCode,
     pub startLineNumber: u32,
     pub startColumn: u32,
     pub endLineNumber: u32,
     pub endColumn: u32,
     pub severity: MarkerSeverity,
+    pub tags: Vec<MarkerTag>,
+    pub fixes: Vec<CodeAction>, // customized
+    /// Reported by a diagnostic that may have false positives.
+    pub experimental: bool, // customized
+}
+
+#[derive(Serialize, Hash)]
+pub struct DiagnosticCode {
+    pub value: String,
+    pub target: String,
 }
 
 #[allow(dead_code)]
-#[derive(Serialize_repr)]
+#[derive(Serialize_repr, Hash)]
+#[repr(u8)]
+pub enum MarkerTag {
+    Unnecessary = 1,
+    Deprecated = 2,
+}
+
+#[derive(Serialize, Hash)]
+pub struct CodeAction {
+    pub title: String,
+    pub kind: String,
+    pub edit: WorkspaceEdit,
+}
+
+#[derive(Serialize, Hash)]
+pub struct WorkspaceEdit {
+    pub edits: Vec<WorkspaceEditItem>,
+}
+
+#[derive(Serialize, Hash)]
+#[serde(untagged)]
+pub enum WorkspaceEditItem {
+    Text(WorkspaceTextEdit),
+    File(WorkspaceFileEdit),
+}
+
+#[derive(Serialize, Hash)]
+pub struct WorkspaceTextEdit {
+    pub resource: String,
+    pub edit: TextEdit,
+}
+
+#[derive(Serialize, Hash)]
+pub struct WorkspaceFileEdit {
+    pub oldUri: Option<String>,
+    pub newUri: Option<String>,
+    pub options: WorkspaceFileEditOptions,
+}
+
+#[derive(Serialize, Hash)]
+pub struct WorkspaceFileEditOptions {
+    pub overwrite: bool,
+    pub ignoreIfExists: bool,
+}
+
+#[derive(Serialize)]
+pub struct SsrResult {
+    pub edit: WorkspaceEdit,
+    /// The replaced ranges.
+    pub matches: Vec<Location>,
+    pub error: Option<SsrError>,
+}
+
+#[derive(Serialize)]
+pub struct SsrError {
+    pub kind: SsrErrorKind,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SsrErrorKind {
+    /// The pattern or the template does not parse.
+    Parse,
+    /// Anything else, e.g. a path that does not resolve.
+    Other,
+}
+
+#[derive(Serialize)]
+pub struct CrateGraphView {
+    /// The graph in Graphviz DOT format.
+    pub dot: String,
+    pub nodes: Vec<CrateNode>,
+    pub edges: Vec<CrateEdge>,
+}
+
+#[derive(Serialize)]
+pub struct CrateNode {
+    pub id: u32,
+    pub name: Option<String>,
+    pub root: String,
+    pub isLibrary: bool,
+}
+
+#[derive(Serialize)]
+pub struct CrateEdge {
+    pub from: u32,
+    pub to: u32,
+    /// The name the dependency is imported under.
+    pub name: String,
+}
+
+#[derive(Serialize)]
+pub struct Status {
+    /// Statistics of the salsa database, as printed by rust-analyzer.
+    pub analysis: String,
+    pub files: usize,
+    pub crates: usize,
+    /// Size of the wasm linear memory.
+    pub memoryBytes: usize,
+    pub queries: Vec<QueryTiming>,
+}
+
+#[derive(Serialize)]
+pub struct QueryTiming {
+    pub name: String,
+    pub count: u32,
+    pub totalMs: f64,
+    pub maxMs: f64,
+}
+
+/// A file in the Chrome trace event format.
+#[derive(Serialize)]
+pub struct Trace {
+    pub traceEvents: Vec<TraceEvent>,
+    pub displayTimeUnit: &'static str,
+}
+
+/// A complete (`"X"`) event, with timestamps in microseconds.
+#[derive(Serialize, Clone)]
+pub struct TraceEvent {
+    pub name: &'static str,
+    pub cat: &'static str,
+    pub ph: &'static str,
+    pub ts: f64,
+    pub dur: f64,
+    pub pid: u32,
+    pub tid: u64,
+}
+
+#[derive(Serialize)]
+pub struct WorkspaceDiagnosticReport {
+    pub items: Vec<FileDiagnosticReport>,
+}
+
+#[derive(Serialize)]
+pub struct FileDiagnosticReport {
+    pub uri: String,
+    pub kind: DiagnosticReportKind,
+    pub resultId: String,
+    pub diagnostics: Option<Vec<Diagnostic>>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DiagnosticReportKind {
+    Full,
+    Unchanged,
+}
+
+#[derive(Serialize_repr, Hash)]
 #[repr(u8)]
 pub enum MarkerSeverity {
     Hint = 1,
@@ -91,8 +280,9 @@ pub struct RenameLocation {
     pub text: String,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Clone)]
 pub struct CompletionItem {
+    pub id: usize, // customized
     pub label: String,
     pub range: Range,
     pub kind: CompletionItemKind,
@@ -101,11 +291,27 @@ pub struct CompletionItem {
     pub insertTextRules: CompletionItemInsertTextRule,
     pub documentation: Option<MarkdownString>,
     pub filterText: String,
+    pub sortText: String,
+    pub preselect: bool,
+    pub tags: Vec<CompletionItemTag>,
+    pub command: Option<Command>,
     pub additionalTextEdits: Vec<TextEdit>,
 }
 
+#[derive(Serialize)]
+pub struct ResolvedCompletionItem {
+    pub documentation: Option<MarkdownString>,
+    pub additionalTextEdits: Vec<TextEdit>,
+}
+
+#[derive(Serialize_repr, Clone, Copy)]
+#[repr(u8)]
+pub enum CompletionItemTag {
+    Deprecated = 1,
+}
+
 #[allow(dead_code)]
-#[derive(Serialize_repr)]
+#[derive(Serialize_repr, Clone, Copy)]
 #[repr(u8)]
 pub enum CompletionItemKind {
     Method = 0,
@@ -139,7 +345,7 @@ pub enum CompletionItemKind {
 }
 
 #[allow(dead_code)]
-#[derive(Serialize_repr)]
+#[derive(Serialize_repr, Clone, Copy)]
 #[repr(u8)]
 pub enum CompletionItemInsertTextRule {
     None = 0,
@@ -156,7 +362,9 @@ pub enum CompletionItemInsertTextRule {
 
 #[derive(Serialize)]
 pub struct ParameterInformation {
-    pub label: String,
+    /// Start and end offset of the parameter in the signature label.
+    pub label: [usize; 2],
+    pub documentation: Option<MarkdownString>,
 }
 
 #[derive(Serialize)]
@@ -168,14 +376,54 @@ pub struct SignatureInformation {
 
 #[derive(Serialize)]
 pub struct SignatureHelp {
-    pub signatures: [SignatureInformation; 1],
+    pub signatures: Vec<SignatureInformation>,
     pub activeSignature: u32,
     pub activeParameter: Option<usize>,
 }
 
+#[derive(Serialize, Clone)]
+pub struct Location {
+    pub uri: String,
+    pub range: Range,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum MoveDirection {
+    Up,
+    Down,
+}
+
+/// Which files `references` searches.
+#[derive(Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceScope {
+    File,
+    /// All files of the user crates.
+    Crates,
+    /// Everything, including the bundled sysroot.
+    All,
+}
+
+#[derive(Serialize)]
+pub struct Reference {
+    pub uri: String,
+    pub range: Range,
+    pub category: Option<ReferenceCategory>, // customized
+}
+
+#[derive(Serialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceCategory {
+    Declaration,
+    Read,
+    Write,
+}
+
 #[derive(Serialize)]
 pub struct LocationLink {
     pub originSelectionRange: Range,
+    pub uri: String,
     pub range: Range,
     pub targetSelectionRange: Range,
 }
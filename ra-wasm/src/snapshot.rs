@@ -0,0 +1,192 @@
+//! Snapshots of the analysis inputs, for `WorldState::export_state` and
+//! `WorldState::import_state`.
+//!
+//! Only the inputs are saved: the virtual files, the source roots, the crate
+//! graph and the config. Salsa can't persist derived data such as def maps,
+//! these are recomputed on demand after a restore. Snapshots are encoded as
+//! JSON through `JSON.stringify`.
+use std::{collections::HashSet, sync::Arc};
+
+use ide::{AnalysisHost, Change, CrateGraph, CrateId, FileId, SourceRoot};
+use ide_db::base_db::{CrateName, Dependency, FileSet, SourceDatabase, SourceDatabaseExt, VfsPath};
+use serde::{Deserialize, Serialize};
+
+use crate::WorldState;
+
+pub(crate) const VERSION: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+pub(crate) struct Snapshot {
+    version: u32,
+    main_file: u32,
+    source_roots: Vec<SourceRootData>,
+    crates: Vec<CrateData>,
+    /// The config as passed to `set_config`, in JSON. It is kept as is,
+    /// because `serde_wasm_bindgen` turns the maps in it into `Map`s, which
+    /// `JSON.stringify` doesn't support.
+    pub(crate) config: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SourceRootData {
+    is_library: bool,
+    files: Vec<FileData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct FileData {
+    id: u32,
+    path: String,
+    text: String,
+}
+
+/// All crates are created by `create_crate`, so their edition and cfg
+/// options don't need to be saved.
+#[derive(Serialize, Deserialize)]
+struct CrateData {
+    name: String,
+    root_file: u32,
+    dependencies: Vec<DependencyData>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DependencyData {
+    name: String,
+    /// Index into `Snapshot::crates`.
+    krate: usize,
+}
+
+pub(crate) fn export(world: &WorldState) -> Snapshot {
+    let db = world.host.raw_database();
+
+    let source_roots = world
+        .source_roots()
+        .iter()
+        .map(|source_root| SourceRootData {
+            is_library: source_root.is_library,
+            files: source_root
+                .iter()
+                .map(|file_id| FileData {
+                    id: file_id.0,
+                    path: source_root.path_for_file(&file_id).unwrap().to_string(),
+                    text: db.file_text(file_id).to_string(),
+                })
+                .collect(),
+        })
+        .collect();
+
+    let crate_graph = db.crate_graph();
+    let ids: Vec<CrateId> = crate_graph.iter().collect();
+    let crates = ids
+        .iter()
+        .map(|&krate| {
+            let data = &crate_graph[krate];
+            CrateData {
+                name: data.display_name.as_ref().map(|it| it.to_string()).unwrap_or_default(),
+                root_file: data.root_file_id.0,
+                dependencies: data
+                    .dependencies
+                    .iter()
+                    .map(|dep| DependencyData {
+                        name: dep.name.to_string(),
+                        krate: ids.iter().position(|&it| it == dep.crate_id).unwrap(),
+                    })
+                    .collect(),
+            }
+        })
+        .collect();
+
+    Snapshot {
+        version: VERSION,
+        main_file: world.file_id.0,
+        source_roots,
+        crates,
+        config: world.raw_config.clone(),
+    }
+}
+
+pub(crate) fn restore(snapshot: Snapshot) -> Result<(AnalysisHost, FileId), String> {
+    if snapshot.version != VERSION {
+        return Err(format!("unsupported snapshot version {}", snapshot.version));
+    }
+
+    let mut change = Change::new();
+    let mut roots = Vec::new();
+    let mut file_ids = HashSet::new();
+    for root in snapshot.source_roots {
+        let mut file_set = FileSet::default();
+        for file in root.files {
+            if !file.path.starts_with('/') {
+                return Err(format!("invalid path {}", file.path));
+            }
+            if !file_ids.insert(file.id) {
+                return Err(format!("duplicate file {}", file.id));
+            }
+            let file_id = FileId(file.id);
+            file_set.insert(file_id, VfsPath::new_virtual_path(file.path));
+            change.change_file(file_id, Some(Arc::new(file.text)));
+        }
+        roots.push(if root.is_library {
+            SourceRoot::new_library(file_set)
+        } else {
+            SourceRoot::new_local(file_set)
+        });
+    }
+    change.set_roots(roots);
+    let root_files = snapshot.crates.iter().map(|krate| krate.root_file);
+    if let Some(id) =
+        std::iter::once(snapshot.main_file).chain(root_files).find(|id| !file_ids.contains(id))
+    {
+        return Err(format!("unknown file {}", id));
+    }
+
+    let mut crate_graph = CrateGraph::default();
+    let ids: Vec<CrateId> = snapshot
+        .crates
+        .iter()
+        .map(|krate| crate::create_crate(&mut crate_graph, &krate.name, FileId(krate.root_file)))
+        .collect();
+    for (&krate, data) in ids.iter().zip(&snapshot.crates) {
+        for dep in &data.dependencies {
+            let name = CrateName::new(&dep.name)
+                .map_err(|_| format!("invalid crate name {}", dep.name))?;
+            let dep_id =
+                *ids.get(dep.krate).ok_or_else(|| format!("unknown crate {}", dep.krate))?;
+            crate_graph
+                .add_dep(krate, Dependency::new(name, dep_id))
+                .map_err(|_| format!("cyclic dependency on {}", dep.name))?;
+        }
+    }
+    change.set_crate_graph(crate_graph);
+
+    let mut host = AnalysisHost::default();
+    host.apply_change(change);
+    Ok((host, FileId(snapshot.main_file)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(file_ids: &[u32], main_file: u32, root_file: u32) -> Snapshot {
+        let files = file_ids
+            .iter()
+            .map(|&id| FileData { id, path: format!("/{}.rs", id), text: String::new() })
+            .collect();
+        Snapshot {
+            version: VERSION,
+            main_file,
+            source_roots: vec![SourceRootData { is_library: false, files }],
+            crates: vec![CrateData { name: "a".to_string(), root_file, dependencies: Vec::new() }],
+            config: None,
+        }
+    }
+
+    #[test]
+    fn restore_checks_file_ids() {
+        assert!(restore(snapshot(&[0, 1], 1, 0)).is_ok());
+        assert_eq!(restore(snapshot(&[0, 0], 0, 0)).err().unwrap(), "duplicate file 0");
+        assert_eq!(restore(snapshot(&[0], 1, 0)).err().unwrap(), "unknown file 1");
+        assert_eq!(restore(snapshot(&[0], 0, 2)).err().unwrap(), "unknown file 2");
+    }
+}
@@ -0,0 +1,103 @@
+//! Timings of the calls into `WorldState`, reported by `WorldState::status`,
+//! and the opt-in trace of those calls exported by `WorldState::export_trace`.
+//! All of them run on the thread calling into `WorldState`.
+use std::{cell::RefCell, collections::BTreeMap, rc::Rc, time::Duration};
+
+use instant::Instant;
+
+use crate::return_types::{QueryTiming, TraceEvent};
+
+/// Spans beyond this are dropped, so a forgotten trace can't eat all memory.
+const MAX_TRACE_EVENTS: usize = 100_000;
+
+#[derive(Default)]
+pub(crate) struct Timings {
+    inner: Rc<RefCell<Inner>>,
+}
+
+#[derive(Default)]
+struct Inner {
+    queries: BTreeMap<&'static str, Stats>,
+    trace: Option<Trace>,
+}
+
+#[derive(Default, Clone, Copy)]
+struct Stats {
+    count: u32,
+    total: Duration,
+    max: Duration,
+}
+
+struct Trace {
+    epoch: Instant,
+    events: Vec<TraceEvent>,
+}
+
+/// Records the time until it is dropped under the name of the query.
+pub(crate) struct Timer {
+    inner: Rc<RefCell<Inner>>,
+    name: &'static str,
+    start: Instant,
+}
+
+impl Timings {
+    pub(crate) fn start(&self, name: &'static str) -> Timer {
+        log::debug!("{}", name);
+        Timer { inner: self.inner.clone(), name, start: Instant::now() }
+    }
+
+    pub(crate) fn report(&self) -> Vec<QueryTiming> {
+        self.inner
+            .borrow()
+            .queries
+            .iter()
+            .map(|(&name, stats)| QueryTiming {
+                name: name.to_string(),
+                count: stats.count,
+                totalMs: stats.total.as_secs_f64() * 1000.0,
+                maxMs: stats.max.as_secs_f64() * 1000.0,
+            })
+            .collect()
+    }
+
+    /// Starts a new trace, dropping the previous one, or stops tracing.
+    pub(crate) fn set_tracing(&self, enabled: bool) {
+        self.inner.borrow_mut().trace =
+            enabled.then(|| Trace { epoch: Instant::now(), events: Vec::new() });
+    }
+
+    pub(crate) fn trace_events(&self) -> Vec<TraceEvent> {
+        self.inner.borrow().trace.as_ref().map_or_else(Vec::new, |trace| trace.events.clone())
+    }
+}
+
+impl Drop for Timer {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        let mut inner = self.inner.borrow_mut();
+
+        let stats = inner.queries.entry(self.name).or_default();
+        stats.count += 1;
+        stats.total += elapsed;
+        stats.max = stats.max.max(elapsed);
+
+        if let Some(trace) = &mut inner.trace {
+            // Calls that were already running when tracing started are skipped.
+            if self.start >= trace.epoch && trace.events.len() < MAX_TRACE_EVENTS {
+                trace.events.push(TraceEvent {
+                    name: self.name,
+                    cat: "request",
+                    ph: "X",
+                    ts: micros(self.start - trace.epoch),
+                    dur: micros(elapsed),
+                    pid: 1,
+                    tid: 0,
+                });
+            }
+        }
+    }
+}
+
+fn micros(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1_000_000.0
+}
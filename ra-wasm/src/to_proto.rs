@@ -1,5 +1,7 @@
 //! Conversion of rust-analyzer specific types to return_types equivalents.
-use crate::return_types;
+use syntax::{ast, AstNode};
+
+use crate::{config, inlay_hints, return_types};
 
 pub(crate) fn text_range(
     range: ide::TextRange,
@@ -16,6 +18,14 @@ pub(crate) fn text_range(
     }
 }
 
+pub(crate) fn position(
+    offset: ide::TextSize,
+    line_index: &ide::LineIndex,
+) -> return_types::Position {
+    let line_col = line_index.line_col(offset);
+    return_types::Position { lineNumber: line_col.line + 1, column: line_col.col + 1 }
+}
+
 pub(crate) fn completion_item_kind(
     kind: ide::CompletionItemKind,
 ) -> return_types::CompletionItemKind {
@@ -54,6 +64,99 @@ pub(crate) fn completion_item_kind(
     }
 }
 
+/// `target` is the name and location of the type the hint refers to, it
+/// becomes a clickable part of the label.
+pub(crate) fn inlay_hint(
+    hint: &inlay_hints::InlayHint,
+    target: Option<(String, return_types::Location)>,
+    render_colons: bool,
+    line_index: &ide::LineIndex,
+) -> return_types::InlayHint {
+    use inlay_hints::InlayKind;
+    use return_types::InlayHintType;
+
+    let label = hint.label.clone();
+    let (hint_type, label, padding_left, padding_right) = match hint.kind {
+        InlayKind::Type if render_colons => {
+            (InlayHintType::Type, format!(": {}", label), false, false)
+        }
+        InlayKind::Type => (InlayHintType::Type, label, true, false),
+        InlayKind::Parameter if render_colons => {
+            (InlayHintType::Parameter, format!("{}:", label), false, true)
+        }
+        InlayKind::Parameter => (InlayHintType::Parameter, label, false, true),
+        InlayKind::Chaining => (InlayHintType::Chaining, label, true, false),
+        InlayKind::ClosureReturnType => (InlayHintType::ClosureReturnType, label, true, false),
+        InlayKind::Lifetime => (InlayHintType::Lifetime, label, false, true),
+        InlayKind::GenericParamList => (InlayHintType::Lifetime, label, false, false),
+    };
+    let range = text_range(hint.range, line_index);
+    // Parameter hints go in front of the argument, all others after the
+    // annotated syntax.
+    let position = match hint.kind {
+        InlayKind::Parameter => {
+            return_types::Position { lineNumber: range.startLineNumber, column: range.startColumn }
+        }
+        _ => return_types::Position { lineNumber: range.endLineNumber, column: range.endColumn },
+    };
+
+    return_types::InlayHint {
+        labelParts: inlay_hint_label_parts(&label, target),
+        label,
+        hint_type,
+        range,
+        position,
+        paddingLeft: padding_left,
+        paddingRight: padding_right,
+    }
+}
+
+fn inlay_hint_label_parts(
+    label: &str,
+    target: Option<(String, return_types::Location)>,
+) -> Vec<return_types::InlayHintLabelPart> {
+    use return_types::InlayHintLabelPart;
+
+    let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+    let found = target.and_then(|(name, location)| {
+        let (start, _) = label.match_indices(name.as_str()).find(|&(start, _)| {
+            let end = start + name.len();
+            !label[..start].ends_with(is_ident_char) && !label[end..].starts_with(is_ident_char)
+        })?;
+        Some((start..start + name.len(), location))
+    });
+    let (range, location) = match found {
+        Some(it) => it,
+        None => return vec![InlayHintLabelPart { value: label.to_string(), location: None }],
+    };
+
+    let mut parts = Vec::new();
+    if range.start > 0 {
+        parts.push(InlayHintLabelPart { value: label[..range.start].to_string(), location: None });
+    }
+    parts.push(InlayHintLabelPart {
+        value: label[range.clone()].to_string(),
+        location: Some(location),
+    });
+    if range.end < label.len() {
+        parts.push(InlayHintLabelPart { value: label[range.end..].to_string(), location: None });
+    }
+    parts
+}
+
+pub(crate) fn diagnostic_code(code: &str) -> return_types::DiagnosticCode {
+    let value = code.to_string();
+    let is_rustc_code = value.len() == 5
+        && value.starts_with('E')
+        && value[1..].chars().all(|c| c.is_ascii_digit());
+    let target = if is_rustc_code {
+        format!("https://doc.rust-lang.org/error-index.html#{}", value)
+    } else {
+        format!("https://rust-analyzer.github.io/manual.html#{}", value)
+    };
+    return_types::DiagnosticCode { value, target }
+}
+
 pub(crate) fn severity(s: ide::Severity) -> return_types::MarkerSeverity {
     match s {
         ide::Severity::Error => return_types::MarkerSeverity::Error,
@@ -61,6 +164,37 @@ pub(crate) fn severity(s: ide::Severity) -> return_types::MarkerSeverity {
     }
 }
 
+pub(crate) fn severity_override(s: config::SeverityDef) -> return_types::MarkerSeverity {
+    match s {
+        config::SeverityDef::Hint => return_types::MarkerSeverity::Hint,
+        config::SeverityDef::Info => return_types::MarkerSeverity::Info,
+        config::SeverityDef::Warning => return_types::MarkerSeverity::Warning,
+        config::SeverityDef::Error => return_types::MarkerSeverity::Error,
+    }
+}
+
+pub(crate) fn reference_category(
+    category: ide_db::search::ReferenceCategory,
+) -> return_types::ReferenceCategory {
+    match category {
+        ide_db::search::ReferenceCategory::Read => return_types::ReferenceCategory::Read,
+        ide_db::search::ReferenceCategory::Write => return_types::ReferenceCategory::Write,
+    }
+}
+
+/// `SsrError` only carries a message, which it prefixes with "Parse error: "
+/// whatever went wrong. Queries that parse on their own failed later on, e.g.
+/// when resolving their paths.
+pub(crate) fn ssr_error(query: &str, err: ide::SsrError) -> return_types::SsrError {
+    let kind = match query.parse::<ide_ssr::SsrRule>() {
+        Ok(_) => return_types::SsrErrorKind::Other,
+        Err(_) => return_types::SsrErrorKind::Parse,
+    };
+    let message = err.to_string();
+    let message = message.strip_prefix("Parse error: ").unwrap_or(&message).to_string();
+    return_types::SsrError { kind, message }
+}
+
 pub(crate) fn text_edit(indel: &ide::Indel, line_index: &ide::LineIndex) -> return_types::TextEdit {
     let text = indel.insert.clone();
     return_types::TextEdit { range: text_range(indel.delete, line_index), text }
@@ -70,35 +204,40 @@ pub(crate) fn text_edits(edit: ide::TextEdit, ctx: &ide::LineIndex) -> Vec<retur
     edit.iter().map(|atom| text_edit(atom, ctx)).collect()
 }
 
-pub(crate) fn completion_item(
-    item: ide::CompletionItem,
+/// Converts the parts of the completion items needed to show them in the list;
+/// documentation and import edits are filled in by `resolve_completion`.
+pub(crate) fn completion_items(
+    items: &[ide::CompletionItem],
+    file_text: &str,
     line_index: &ide::LineIndex,
-) -> return_types::CompletionItem {
-    let mut additional_text_edits = Vec::new();
-    let mut edit = None;
-    // LSP does not allow arbitrary edits in completion, so we have to do a
-    // non-trivial mapping here.
-    for atom_edit in item.text_edit().iter() {
-        if item.source_range().contains_range(atom_edit.delete) {
-            edit = Some(if atom_edit.delete == item.source_range() {
-                text_edit(atom_edit, line_index)
-            } else {
-                assert!(item.source_range().end() == atom_edit.delete.end());
-                let range1 =
-                    ide::TextRange::new(atom_edit.delete.start(), item.source_range().start());
-                let range2 = item.source_range();
-                let edit1 = ide::Indel::replace(range1, String::new());
-                let edit2 = ide::Indel::replace(range2, atom_edit.insert.clone());
-                additional_text_edits.push(text_edit(&edit1, line_index));
-                text_edit(&edit2, line_index)
-            })
-        } else {
-            edit = Some(text_edit(atom_edit, line_index));
-        }
+) -> Vec<return_types::CompletionItem> {
+    let max_relevance = items.iter().map(|it| it.relevance().score()).max().unwrap_or_default();
+    let mut res = Vec::with_capacity(items.len());
+    for (id, item) in items.iter().enumerate() {
+        completion_item(&mut res, id, item, max_relevance, file_text, line_index);
     }
-    let return_types::TextEdit { range, text } = edit.unwrap();
+    res
+}
 
-    return_types::CompletionItem {
+fn completion_item(
+    acc: &mut Vec<return_types::CompletionItem>,
+    id: usize,
+    item: &ide::CompletionItem,
+    max_relevance: u32,
+    file_text: &str,
+    line_index: &ide::LineIndex,
+) {
+    let (edit, additional_text_edits) = completion_text_edits(
+        item.source_range(),
+        item.text_edit(),
+        item.is_snippet(),
+        file_text,
+        line_index,
+    );
+    let return_types::TextEdit { range, text } = edit;
+
+    let mut res = return_types::CompletionItem {
+        id,
         kind: completion_item_kind(item.kind()),
         label: item.label().to_string(),
         range,
@@ -109,50 +248,220 @@ pub(crate) fn completion_item(
         } else {
             return_types::CompletionItemInsertTextRule::None
         },
-        documentation: item.documentation().map(|doc| markdown_string(doc.as_str())),
+        documentation: None,
         filterText: item.lookup().to_string(),
+        sortText: String::new(),
+        preselect: false,
+        tags: Vec::new(),
+        command: None,
         additionalTextEdits: additional_text_edits,
+    };
+    set_score(&mut res, max_relevance, item.relevance());
+
+    if item.deprecated() {
+        res.tags.push(return_types::CompletionItemTag::Deprecated);
+    }
+
+    if item.trigger_call_info() {
+        res.command = Some(return_types::Command {
+            id: "editor.action.triggerParameterHints".into(),
+            title: "triggerParameterHints".into(),
+            positions: Vec::new(),
+        });
+    }
+
+    if let Some((mutability, relevance)) = item.ref_match() {
+        let prefix = format!("&{}", mutability.as_keyword_for_ref());
+        let mut res_with_ref = res.clone();
+        res_with_ref.label = format!("{}{}", prefix, res_with_ref.label);
+        res_with_ref.insertText = format!("{}{}", prefix, res_with_ref.insertText);
+        set_score(&mut res_with_ref, max_relevance, relevance);
+        acc.push(res_with_ref);
+    }
+
+    acc.push(res);
+
+    fn set_score(
+        res: &mut return_types::CompletionItem,
+        max_relevance: u32,
+        relevance: ide::CompletionRelevance,
+    ) {
+        if relevance.is_relevant() && relevance.score() == max_relevance {
+            res.preselect = true;
+        }
+        // Monaco sorts ascending, so the score is inverted. It is zero padded
+        // to make the lexicographic order match the numeric one.
+        res.sortText = format!("{:08x}", relevance.score() ^ 0xFF_FF_FF_FF);
+    }
+}
+
+/// Monaco requires the main edit of a completion to replace a single-line
+/// range containing the cursor, while rust-analyzer may produce arbitrary
+/// `Indel`s. The main edit is therefore always made to replace the source
+/// range of the item: the parts of the `Indel`s falling inside of it are
+/// merged into its text, everything else becomes an additional edit.
+fn completion_text_edits(
+    source_range: ide::TextRange,
+    edit: &ide::TextEdit,
+    is_snippet: bool,
+    file_text: &str,
+    line_index: &ide::LineIndex,
+) -> (return_types::TextEdit, Vec<return_types::TextEdit>) {
+    let mut additional_text_edits = Vec::new();
+    let mut text = String::new();
+    let mut offset = source_range.start();
+
+    for indel in edit.iter() {
+        let delete = indel.delete;
+        let touches_source_range = delete.contains_range(source_range)
+            || source_range.contains_range(delete)
+            || delete.intersect(source_range).map_or(false, |it| !it.is_empty());
+        if !touches_source_range {
+            additional_text_edits.push(text_edit(indel, line_index));
+            continue;
+        }
+
+        if delete.start() < source_range.start() {
+            let range = ide::TextRange::new(delete.start(), source_range.start());
+            additional_text_edits.push(text_edit(&ide::Indel::delete(range), line_index));
+        }
+        if delete.end() > source_range.end() {
+            let range = ide::TextRange::new(source_range.end(), delete.end());
+            additional_text_edits.push(text_edit(&ide::Indel::delete(range), line_index));
+        }
+
+        let start = delete.start().max(source_range.start()).max(offset);
+        let end = delete.end().min(source_range.end()).max(start);
+        push_kept_text(&mut text, &file_text[ide::TextRange::new(offset, start)], is_snippet);
+        text.push_str(&indel.insert);
+        offset = end;
+    }
+    let kept = &file_text[ide::TextRange::new(offset, source_range.end())];
+    push_kept_text(&mut text, kept, is_snippet);
+
+    let edit = return_types::TextEdit { range: text_range(source_range, line_index), text };
+    (edit, additional_text_edits)
+}
+
+/// Appends original source text to the main edit, escaping it for snippets.
+fn push_kept_text(text: &mut String, kept: &str, is_snippet: bool) {
+    if !is_snippet {
+        text.push_str(kept);
+        return;
+    }
+    for c in kept.chars() {
+        if matches!(c, '$' | '}' | '\\') {
+            text.push('\\');
+        }
+        text.push(c);
     }
 }
 
 pub(crate) fn signature_information(
     call_info: ide::CallInfo,
 ) -> return_types::SignatureInformation {
-    use return_types::{ParameterInformation, SignatureInformation};
+    let ranges = call_info.parameter_ranges().to_vec();
+    signature(call_info.signature, &ranges, call_info.doc.as_deref())
+}
+
+/// The signature of `func`, an implementation of a called trait method,
+/// which has no `CallInfo` of its own. With the method call syntax the self
+/// parameter is not one of the arguments.
+pub(crate) fn fn_signature_information(
+    func: &ast::Fn,
+    is_method_call: bool,
+    doc: Option<&str>,
+) -> return_types::SignatureInformation {
+    let mut label = String::from("fn ");
+    if let Some(name) = func.name() {
+        label.push_str(&name.syntax().text().to_string());
+    }
+    if let Some(generics) = func.generic_param_list() {
+        label.push_str(&generics.syntax().text().to_string());
+    }
+
+    let mut params = Vec::new();
+    if let Some(param_list) = func.param_list() {
+        if let Some(self_param) = param_list.self_param() {
+            params.push((self_param.syntax().text().to_string(), !is_method_call));
+        }
+        params.extend(param_list.params().map(|it| (it.syntax().text().to_string(), true)));
+    }
+    label.push('(');
+    let mut ranges = Vec::new();
+    for (i, (param, is_argument)) in params.iter().enumerate() {
+        if i > 0 {
+            label.push_str(", ");
+        }
+        let start = ide::TextSize::of(label.as_str());
+        label.push_str(param);
+        if *is_argument {
+            ranges.push(ide::TextRange::at(start, ide::TextSize::of(param.as_str())));
+        }
+    }
+    label.push(')');
+
+    if let Some(ret_type) = func.ret_type() {
+        label.push(' ');
+        label.push_str(&ret_type.syntax().text().to_string());
+    }
+    signature(label, &ranges, doc)
+}
 
-    let label = call_info.signature.clone();
-    let documentation = call_info.doc.as_ref().map(|it| markdown_string(it));
+fn signature(
+    label: String,
+    ranges: &[ide::TextRange],
+    doc: Option<&str>,
+) -> return_types::SignatureInformation {
+    use return_types::{ParameterInformation, SignatureInformation};
 
-    let parameters: Vec<ParameterInformation> = call_info
-        .parameter_labels()
-        .into_iter()
-        .map(|param| ParameterInformation { label: param.to_string() })
+    // Monaco expects the offsets of the parameters in UTF-16 code units.
+    let utf16_offset = |offset: ide::TextSize| label[..usize::from(offset)].encode_utf16().count();
+    let parameters: Vec<ParameterInformation> = ranges
+        .iter()
+        .map(|&range| ParameterInformation {
+            label: [utf16_offset(range.start()), utf16_offset(range.end())],
+            documentation: doc
+                .and_then(|doc| parameter_documentation(doc, &label[range]))
+                .map(|doc| markdown_string(&doc)),
+        })
         .collect();
 
+    let documentation = doc.map(markdown_string);
     SignatureInformation { label, documentation, parameters }
 }
 
-pub(crate) fn location_links(
-    nav_info: ide::RangeInfo<Vec<ide::NavigationTarget>>,
+/// Finds the description of a parameter in an `# Arguments` style list,
+/// ``* `name` - description``, of the function's documentation.
+fn parameter_documentation(doc: &str, param: &str) -> Option<String> {
+    let name = param.split(':').next()?.trim().trim_start_matches("mut ").trim();
+    if name.is_empty() {
+        return None;
+    }
+    let name = format!("`{}`", name);
+    doc.lines().find_map(|line| {
+        let item = line.trim_start().strip_prefix(&['*', '-'][..])?;
+        let description = item.trim_start().strip_prefix(name.as_str())?;
+        let description = description.trim_start().trim_start_matches(&['-', ':'][..]).trim();
+        (!description.is_empty()).then(|| description.to_string())
+    })
+}
+
+/// A link from `origin` to the item at `full_range` in the file at `uri`,
+/// whose `line_index` is given.
+pub(crate) fn location_link(
+    origin: return_types::Range,
+    uri: String,
+    full_range: ide::TextRange,
+    focus_range: ide::TextRange,
     line_index: &ide::LineIndex,
-) -> Vec<return_types::LocationLink> {
-    let selection = text_range(nav_info.range, line_index);
-    nav_info
-        .info
-        .into_iter()
-        .map(|nav| {
-            let range = text_range(nav.full_range, line_index);
-
-            let target_selection_range =
-                nav.focus_range.map(|it| text_range(it, line_index)).unwrap_or(range);
-
-            return_types::LocationLink {
-                originSelectionRange: selection,
-                range,
-                targetSelectionRange: target_selection_range,
-            }
-        })
-        .collect()
+) -> return_types::LocationLink {
+    return_types::LocationLink {
+        originSelectionRange: origin,
+        uri,
+        range: text_range(full_range, line_index),
+        targetSelectionRange: text_range(focus_range, line_index),
+    }
 }
 
 pub(crate) fn symbol_kind(kind: ide::StructureNodeKind) -> return_types::SymbolKind {
@@ -201,7 +510,9 @@ pub(crate) fn folding_range(fold: ide::Fold, ctx: &ide::LineIndex) -> return_typ
     }
 }
 
-fn markdown_string(s: &str) -> return_types::MarkdownString {
+/// Untrusted, so that command links in docs can't run. Callers enable the
+/// command of `doc_links::rewrite_links` once it has handled their links.
+pub(crate) fn markdown_string(s: &str) -> return_types::MarkdownString {
     fn code_line_ignored_by_rustdoc(line: &str) -> bool {
         let trimmed = line.trim();
         trimmed == "#" || trimmed.starts_with("# ") || trimmed.starts_with("#\t")
@@ -227,5 +538,173 @@ fn markdown_string(s: &str) -> return_types::MarkdownString {
         processed_lines.push(line);
     }
 
-    return_types::MarkdownString { value: processed_lines.join("\n") }
+    return_types::MarkdownString { value: processed_lines.join("\n"), isTrusted: None }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn complete(fixture: &str) -> (ide::AnalysisHost, ide::FilePosition, String) {
+        let offset = fixture.find("$0").unwrap();
+        let text = fixture.replacen("$0", "", 1);
+        let (host, file_id) =
+            crate::from_single_file(text.clone(), String::new(), String::new(), String::new());
+        let position = ide::FilePosition { file_id, offset: ide::TextSize::from(offset as u32) };
+        (host, position, text)
+    }
+
+    /// Applies the main and the additional edits of the first item whose
+    /// label contains `label`.
+    fn check_completion(fixture: &str, label: &str, expect: &str) {
+        let (host, position, text) = complete(fixture);
+        let analysis = host.analysis();
+        let items = analysis
+            .completions(&config::Config::default().completion(), position)
+            .unwrap()
+            .unwrap();
+        let line_index = analysis.file_line_index(position.file_id).unwrap();
+        let items = completion_items(&items, &text, &line_index);
+        let item = items
+            .into_iter()
+            .find(|it| !it.label.starts_with('&') && it.label.contains(label))
+            .unwrap_or_else(|| panic!("no completion {}", label));
+
+        let mut edits = item.additionalTextEdits;
+        edits.push(return_types::TextEdit { range: item.range, text: item.insertText });
+        assert_eq!(apply(&text, edits), expect);
+    }
+
+    fn apply(text: &str, edits: Vec<return_types::TextEdit>) -> String {
+        let line_index = ide::LineIndex::new(text);
+        let offset = |line: u32, col: u32| {
+            usize::from(line_index.offset(ide::LineCol { line: line - 1, col: col - 1 }))
+        };
+        let mut edits: Vec<_> = edits
+            .into_iter()
+            .map(|edit| {
+                let range = edit.range;
+                let start = offset(range.startLineNumber, range.startColumn);
+                let end = offset(range.endLineNumber, range.endColumn);
+                (start, end, edit.text)
+            })
+            .collect();
+        edits.sort_by_key(|&(start, ..)| std::cmp::Reverse(start));
+
+        let mut res = text.to_string();
+        for (start, end, insert) in edits {
+            res.replace_range(start..end, &insert);
+        }
+        res
+    }
+
+    fn range(start: u32, end: u32) -> ide::TextRange {
+        ide::TextRange::new(start.into(), end.into())
+    }
+
+    #[test]
+    fn postfix_completion() {
+        check_completion(
+            "fn main() { let x = 1; x.dbg$0 }",
+            "dbg",
+            "fn main() { let x = 1; dbg!(x) }",
+        );
+    }
+
+    #[test]
+    fn self_completion() {
+        check_completion(
+            "struct S { field: u32 } impl S { fn f(&self) { fie$0 } }",
+            "field",
+            "struct S { field: u32 } impl S { fn f(&self) { self.field } }",
+        );
+    }
+
+    #[test]
+    fn auto_import_completion() {
+        let fixture = "mod m { pub struct Bazz; }\nfn main() { Baz$0 }";
+        check_completion(fixture, "Bazz", "mod m { pub struct Bazz; }\nfn main() { Bazz }");
+
+        // The import is only added by `resolve_completion`.
+        let (host, position, text) = complete(fixture);
+        let analysis = host.analysis();
+        let config = config::Config::default().completion();
+        let items = analysis.completions(&config, position).unwrap().unwrap();
+        let import = items.iter().find_map(|it| it.import_to_add()).unwrap();
+        let import_path = &import.import.import_path;
+        let edits = analysis
+            .resolve_completion_edits(
+                &config,
+                position,
+                &import_path.to_string(),
+                import_path.segments().last().unwrap().to_string(),
+            )
+            .unwrap();
+        let line_index = analysis.file_line_index(position.file_id).unwrap();
+        let edits = edits.into_iter().flat_map(|it| text_edits(it, &line_index)).collect();
+        assert!(apply(&text, edits).contains("use m::Bazz;"));
+    }
+
+    #[test]
+    fn overlapping_indels() {
+        let text = "a.b(cde)fg";
+        let line_index = ide::LineIndex::new(text);
+        let edits = [
+            // Overlapping both ends of the source range, `cde`.
+            vec![(range(1, 5), "1"), (range(6, 9), "2")],
+            // Containing the source range.
+            vec![(range(3, 9), "X")],
+            // Inside of the source range, next to an unrelated insertion.
+            vec![(range(0, 0), "0"), (range(5, 6), "D")],
+        ];
+        for indels in edits {
+            let mut builder = ide::TextEdit::builder();
+            for (range, insert) in indels {
+                builder.replace(range, insert.to_string());
+            }
+            let edit = builder.finish();
+            let mut expect = text.to_string();
+            edit.apply(&mut expect);
+
+            let (main, mut additional) =
+                completion_text_edits(range(4, 7), &edit, false, text, &line_index);
+            assert_eq!((main.range.startColumn, main.range.endColumn), (5, 8));
+            additional.push(main);
+            assert_eq!(apply(text, additional), expect);
+        }
+    }
+
+    #[test]
+    fn kept_text_is_escaped_in_snippets() {
+        let text = r"x $a}\ y";
+        let line_index = ide::LineIndex::new(text);
+        let mut builder = ide::TextEdit::builder();
+        builder.insert(2.into(), "${1:it}".to_string());
+        let edit = builder.finish();
+
+        let (main, _) = completion_text_edits(range(2, 6), &edit, true, text, &line_index);
+        assert_eq!(main.text, r"${1:it}\$a\}\\");
+        let (main, _) = completion_text_edits(range(2, 6), &edit, false, text, &line_index);
+        assert_eq!(main.text, r"${1:it}$a}\");
+    }
+
+    #[test]
+    fn impl_signature() {
+        let text = "impl S { fn f<T>(&self, a: u32, b: T) -> u32 {} }";
+        let file = syntax::SourceFile::parse(text).tree();
+        let func = file.syntax().descendants().find_map(ast::Fn::cast).unwrap();
+        let doc = "* `a` - the first";
+
+        let sig = fn_signature_information(&func, true, Some(doc));
+        assert_eq!(sig.label, "fn f<T>(&self, a: u32, b: T) -> u32");
+        let params: Vec<_> =
+            sig.parameters.iter().map(|it| &sig.label[it.label[0]..it.label[1]]).collect();
+        assert_eq!(params, ["a: u32", "b: T"]);
+        assert_eq!(sig.parameters[0].documentation.as_ref().unwrap().value, "the first");
+
+        let sig = fn_signature_information(&func, false, None);
+        let params: Vec<_> =
+            sig.parameters.iter().map(|it| &sig.label[it.label[0]..it.label[1]]).collect();
+        assert_eq!(params, ["&self", "a: u32", "b: T"]);
+    }
 }
@@ -1,46 +1,47 @@
-import 'monaco-editor/esm/vs/editor/browser/controller/coreCommands';
+// The contributions moved to `browser` directories in 0.32.
+import 'monaco-editor/esm/vs/editor/browser/coreCommands';
 import 'monaco-editor/esm/vs/editor/browser/widget/codeEditorWidget';
 import 'monaco-editor/esm/vs/editor/browser/widget/diffEditorWidget';
 import 'monaco-editor/esm/vs/editor/browser/widget/diffNavigator';
-import 'monaco-editor/esm/vs/editor/contrib/anchorSelect/anchorSelect';
-import 'monaco-editor/esm/vs/editor/contrib/bracketMatching/bracketMatching';
-import 'monaco-editor/esm/vs/editor/contrib/caretOperations/caretOperations';
-import 'monaco-editor/esm/vs/editor/contrib/caretOperations/transpose';
-import 'monaco-editor/esm/vs/editor/contrib/clipboard/clipboard';
-import 'monaco-editor/esm/vs/editor/contrib/codeAction/codeActionContributions';
-import 'monaco-editor/esm/vs/editor/contrib/codelens/codelensController';
-import 'monaco-editor/esm/vs/editor/contrib/colorPicker/colorContributions';
-import 'monaco-editor/esm/vs/editor/contrib/comment/comment';
-import 'monaco-editor/esm/vs/editor/contrib/contextmenu/contextmenu';
-import 'monaco-editor/esm/vs/editor/contrib/cursorUndo/cursorUndo';
-import 'monaco-editor/esm/vs/editor/contrib/dnd/dnd';
-import 'monaco-editor/esm/vs/editor/contrib/documentSymbols/documentSymbols';
-import 'monaco-editor/esm/vs/editor/contrib/find/findController';
-import 'monaco-editor/esm/vs/editor/contrib/folding/folding';
-import 'monaco-editor/esm/vs/editor/contrib/fontZoom/fontZoom';
-import 'monaco-editor/esm/vs/editor/contrib/format/formatActions';
-import 'monaco-editor/esm/vs/editor/contrib/gotoError/gotoError';
-import 'monaco-editor/esm/vs/editor/contrib/gotoSymbol/goToCommands';
-import 'monaco-editor/esm/vs/editor/contrib/gotoSymbol/link/goToDefinitionAtPosition';
-import 'monaco-editor/esm/vs/editor/contrib/hover/hover';
-import 'monaco-editor/esm/vs/editor/contrib/inPlaceReplace/inPlaceReplace';
-import 'monaco-editor/esm/vs/editor/contrib/indentation/indentation';
-import 'monaco-editor/esm/vs/editor/contrib/inlayHints/inlayHintsController';
-import 'monaco-editor/esm/vs/editor/contrib/linesOperations/linesOperations';
-import 'monaco-editor/esm/vs/editor/contrib/linkedEditing/linkedEditing';
-import 'monaco-editor/esm/vs/editor/contrib/links/links';
-import 'monaco-editor/esm/vs/editor/contrib/multicursor/multicursor';
-import 'monaco-editor/esm/vs/editor/contrib/parameterHints/parameterHints';
-import 'monaco-editor/esm/vs/editor/contrib/rename/rename';
-import 'monaco-editor/esm/vs/editor/contrib/smartSelect/smartSelect';
-import 'monaco-editor/esm/vs/editor/contrib/snippet/snippetController2';
-import 'monaco-editor/esm/vs/editor/contrib/suggest/suggestController';
-import 'monaco-editor/esm/vs/editor/contrib/toggleTabFocusMode/toggleTabFocusMode';
-import 'monaco-editor/esm/vs/editor/contrib/unusualLineTerminators/unusualLineTerminators';
-import 'monaco-editor/esm/vs/editor/contrib/viewportSemanticTokens/viewportSemanticTokens';
-import 'monaco-editor/esm/vs/editor/contrib/wordHighlighter/wordHighlighter';
-import 'monaco-editor/esm/vs/editor/contrib/wordOperations/wordOperations';
-import 'monaco-editor/esm/vs/editor/contrib/wordPartOperations/wordPartOperations';
+import 'monaco-editor/esm/vs/editor/contrib/anchorSelect/browser/anchorSelect';
+import 'monaco-editor/esm/vs/editor/contrib/bracketMatching/browser/bracketMatching';
+import 'monaco-editor/esm/vs/editor/contrib/caretOperations/browser/caretOperations';
+import 'monaco-editor/esm/vs/editor/contrib/caretOperations/browser/transpose';
+import 'monaco-editor/esm/vs/editor/contrib/clipboard/browser/clipboard';
+import 'monaco-editor/esm/vs/editor/contrib/codeAction/browser/codeActionContributions';
+import 'monaco-editor/esm/vs/editor/contrib/codelens/browser/codelensController';
+import 'monaco-editor/esm/vs/editor/contrib/colorPicker/browser/colorContributions';
+import 'monaco-editor/esm/vs/editor/contrib/comment/browser/comment';
+import 'monaco-editor/esm/vs/editor/contrib/contextmenu/browser/contextmenu';
+import 'monaco-editor/esm/vs/editor/contrib/cursorUndo/browser/cursorUndo';
+import 'monaco-editor/esm/vs/editor/contrib/dnd/browser/dnd';
+import 'monaco-editor/esm/vs/editor/contrib/documentSymbols/browser/documentSymbols';
+import 'monaco-editor/esm/vs/editor/contrib/find/browser/findController';
+import 'monaco-editor/esm/vs/editor/contrib/folding/browser/folding';
+import 'monaco-editor/esm/vs/editor/contrib/fontZoom/browser/fontZoom';
+import 'monaco-editor/esm/vs/editor/contrib/format/browser/formatActions';
+import 'monaco-editor/esm/vs/editor/contrib/gotoError/browser/gotoError';
+import 'monaco-editor/esm/vs/editor/contrib/gotoSymbol/browser/goToCommands';
+import 'monaco-editor/esm/vs/editor/contrib/gotoSymbol/browser/link/goToDefinitionAtPosition';
+import 'monaco-editor/esm/vs/editor/contrib/hover/browser/hover';
+import 'monaco-editor/esm/vs/editor/contrib/inPlaceReplace/browser/inPlaceReplace';
+import 'monaco-editor/esm/vs/editor/contrib/indentation/browser/indentation';
+import 'monaco-editor/esm/vs/editor/contrib/inlayHints/browser/inlayHintsContribution';
+import 'monaco-editor/esm/vs/editor/contrib/linesOperations/browser/linesOperations';
+import 'monaco-editor/esm/vs/editor/contrib/linkedEditing/browser/linkedEditing';
+import 'monaco-editor/esm/vs/editor/contrib/links/browser/links';
+import 'monaco-editor/esm/vs/editor/contrib/multicursor/browser/multicursor';
+import 'monaco-editor/esm/vs/editor/contrib/parameterHints/browser/parameterHints';
+import 'monaco-editor/esm/vs/editor/contrib/rename/browser/rename';
+import 'monaco-editor/esm/vs/editor/contrib/smartSelect/browser/smartSelect';
+import 'monaco-editor/esm/vs/editor/contrib/snippet/browser/snippetController2';
+import 'monaco-editor/esm/vs/editor/contrib/suggest/browser/suggestController';
+import 'monaco-editor/esm/vs/editor/contrib/toggleTabFocusMode/browser/toggleTabFocusMode';
+import 'monaco-editor/esm/vs/editor/contrib/unusualLineTerminators/browser/unusualLineTerminators';
+import 'monaco-editor/esm/vs/editor/contrib/viewportSemanticTokens/browser/viewportSemanticTokens';
+import 'monaco-editor/esm/vs/editor/contrib/wordHighlighter/browser/wordHighlighter';
+import 'monaco-editor/esm/vs/editor/contrib/wordOperations/browser/wordOperations';
+import 'monaco-editor/esm/vs/editor/contrib/wordPartOperations/browser/wordPartOperations';
 import 'monaco-editor/esm/vs/editor/standalone/browser/accessibilityHelp/accessibilityHelp';
 import 'monaco-editor/esm/vs/editor/standalone/browser/iPadShowKeyboard/iPadShowKeyboard';
 import 'monaco-editor/esm/vs/editor/standalone/browser/inspectTokens/inspectTokens';
@@ -62,6 +63,7 @@ import fake_alloc from './fake_alloc.rs';
 
 var state;
 var allTokens;
+var allDiagnostics = [];
 
 self.MonacoEnvironment = {
     getWorkerUrl: () => './editor.worker.bundle.js',
@@ -72,8 +74,55 @@ monaco.languages.register({
     id: modeId,
 });
 
+const sysrootModeId = 'rust-sysroot';
+monaco.languages.register({
+    id: sysrootModeId,
+});
+
+const sysrootSources = {
+    'file:///std/src/lib.rs': fake_std,
+    'file:///core/src/lib.rs': fake_core,
+    'file:///alloc/src/lib.rs': fake_alloc,
+};
+// The model of `uri`, opening the sysroot sources on demand. They get their
+// own language id, so the rust-analyzer providers (which only know the main
+// file) skip them.
+const getModel = (uri) => {
+    const parsed = monaco.Uri.parse(uri);
+    const model = monaco.editor.getModel(parsed);
+    if (model || sysrootSources[uri] === undefined) return model;
+    return monaco.editor.createModel(sysrootSources[uri], sysrootModeId, parsed);
+};
+// Locations may be in any file known to the analysis.
+const toLocations = (list) => list
+    .map(def => ({ ...def, model: getModel(def.uri) }))
+    .filter(def => def.model)
+    .map(({ model, ...def }) => ({ ...def, uri: model.uri }));
+
 const delay = (ms) => new Promise((res) => setTimeout(res, ms));
 
+function openText(text) {
+    const blob = new Blob([text], { type: 'text/plain' });
+    window.open(URL.createObjectURL(blob), '_blank');
+}
+
+function downloadText(name, text) {
+    const link = document.createElement('a');
+    link.href = URL.createObjectURL(new Blob([text], { type: 'application/json' }));
+    link.download = name;
+    link.click();
+    URL.revokeObjectURL(link.href);
+}
+
+function toWorkspaceEdit({ edits }) {
+    const toUri = uri => uri && monaco.Uri.parse(uri);
+    return {
+        edits: edits.map(edit => edit.resource
+            ? { resource: toUri(edit.resource), edit: edit.edit }
+            : { oldUri: toUri(edit.oldUri), newUri: toUri(edit.newUri), options: edit.options }),
+    };
+}
+
 monaco.languages.onLanguage(modeId, async () => {
     console.log(modeId);
 
@@ -81,6 +130,11 @@ monaco.languages.onLanguage(modeId, async () => {
     monaco.languages.setMonarchTokensProvider(modeId, grammar);
 });
 
+monaco.languages.onLanguage(sysrootModeId, () => {
+    monaco.languages.setLanguageConfiguration(sysrootModeId, conf);
+    monaco.languages.setMonarchTokensProvider(sysrootModeId, grammar);
+});
+
 const registerRA = async () => {
     monaco.languages.registerHoverProvider(modeId, {
         provideHover: (_, pos) => state.hover(pos.lineNumber, pos.column),
@@ -113,50 +167,61 @@ const registerRA = async () => {
         },
     });
     monaco.languages.registerReferenceProvider(modeId, {
-        async provideReferences(m, pos, { includeDeclaration }) {
-            const references = await state.references(pos.lineNumber, pos.column, includeDeclaration);
+        async provideReferences(_m, pos, { includeDeclaration }) {
+            const references = await state.references(pos.lineNumber, pos.column, includeDeclaration, 'crates');
             if (references) {
-                return references.map(({ range }) => ({ uri: m.uri, range }));
+                return references.map(({ uri, range }) => ({ uri: monaco.Uri.parse(uri), range }));
             }
         },
     });
     monaco.languages.registerInlayHintsProvider(modeId, {
         async provideInlayHints(model, range, token) {
-            let hints = await state.inlay_hints();
-            return hints.map((hint) => {
-                if (hint.hint_type == 1) {
-                    return {
-                        kind: 1,
-                        position: { column: hint.range.endColumn, lineNumber: hint.range.endLineNumber },
-                        text: `: ${hint.label}`,
-                    };
-                }
-                if (hint.hint_type == 2) {
-                    return {
-                        kind: 2,
-                        position: { column: hint.range.startColumn, lineNumber: hint.range.startLineNumber },
-                        text: `${hint.label}:`,
-                        whitespaceAfter: true,
-                    };
-                }
-            })
+            let hints = await state.inlay_hints(range);
+            return {
+                hints: hints.map((hint) => ({
+                    // Monaco only has type and parameter hints.
+                    kind: hint.hint_type == 2
+                        ? monaco.languages.InlayHintKind.Parameter
+                        : monaco.languages.InlayHintKind.Type,
+                    position: hint.position,
+                    // Monaco opens the `location` of a label part in the
+                    // current editor only if it's the same model, use the
+                    // command which also opens the sysroot sources.
+                    label: hint.labelParts.map(({ value, location }) => ({
+                        label: value,
+                        command: location && {
+                            id: 'rust-analyzer.gotoLocation',
+                            title: 'Go to Type Definition',
+                            arguments: [location],
+                        },
+                    })),
+                    paddingLeft: hint.paddingLeft,
+                    paddingRight: hint.paddingRight,
+                })),
+                dispose() { },
+            };
         }
     });
     monaco.languages.registerDocumentHighlightProvider(modeId, {
-        async provideDocumentHighlights(_, pos) {
-            return await state.references(pos.lineNumber, pos.column, true);
+        async provideDocumentHighlights(m, pos) {
+            const references = await state.references(pos.lineNumber, pos.column, true, 'file');
+            if (references) {
+                return references
+                    .filter(({ uri }) => uri === m.uri.toString())
+                    .map(({ range, category }) => ({
+                        range,
+                        kind: category === 'write' ? monaco.languages.DocumentHighlightKind.Write
+                            : category === 'read' ? monaco.languages.DocumentHighlightKind.Read
+                                : monaco.languages.DocumentHighlightKind.Text,
+                    }));
+            }
         }
     });
     monaco.languages.registerRenameProvider(modeId, {
-        async provideRenameEdits(m, pos, newName) {
-            const edits = await state.rename(pos.lineNumber, pos.column, newName);
-            if (edits) {
-                return {
-                    edits: edits.map(edit => ({
-                        resource: m.uri,
-                        edit,
-                    })),
-                };
+        async provideRenameEdits(_m, pos, newName) {
+            const edit = await state.rename(pos.lineNumber, pos.column, newName);
+            if (edit) {
+                return toWorkspaceEdit(edit);
             }
         },
         async resolveRenameLocation(_, pos) {
@@ -171,6 +236,14 @@ const registerRA = async () => {
                 return { suggestions };
             }
         },
+        async resolveCompletionItem(item) {
+            const resolved = await state.resolve_completion(item.id);
+            if (resolved) {
+                item.documentation = resolved.documentation;
+                item.additionalTextEdits = [...item.additionalTextEdits, ...resolved.additionalTextEdits];
+            }
+            return item;
+        },
     });
     monaco.languages.registerSignatureHelpProvider(modeId, {
         signatureHelpTriggerCharacters: ['(', ','],
@@ -185,9 +258,17 @@ const registerRA = async () => {
     });
     monaco.languages.registerDefinitionProvider(modeId, {
         async provideDefinition(m, pos) {
-            const list = await state.definition(pos.lineNumber, pos.column);
+            const list = await state.definition(pos.lineNumber, pos.column, true);
             if (list) {
-                return list.map(def => ({ ...def, uri: m.uri }));
+                return toLocations(list);
+            }
+        },
+    });
+    monaco.languages.registerDeclarationProvider(modeId, {
+        async provideDeclaration(m, pos) {
+            const list = await state.declaration(pos.lineNumber, pos.column);
+            if (list) {
+                return toLocations(list);
             }
         },
     });
@@ -195,7 +276,7 @@ const registerRA = async () => {
         async provideTypeDefinition(m, pos) {
             const list = await state.type_definition(pos.lineNumber, pos.column);
             if (list) {
-                return list.map(def => ({ ...def, uri: m.uri }));
+                return toLocations(list);
             }
         },
     });
@@ -203,7 +284,7 @@ const registerRA = async () => {
         async provideImplementation(m, pos) {
             const list = await state.goto_implementation(pos.lineNumber, pos.column);
             if (list) {
-                return list.map(def => ({ ...def, uri: m.uri }));
+                return toLocations(list);
             }
         },
     });
@@ -218,6 +299,28 @@ const registerRA = async () => {
             return await state.type_formatting(pos.lineNumber, pos.column, ch);
         }
     });
+    monaco.languages.registerCodeActionProvider(modeId, {
+        provideCodeActions(_m, _range, context) {
+            const actions = [];
+            for (const marker of context.markers) {
+                const diagnostic = allDiagnostics.find((d) =>
+                    d.message === marker.message &&
+                    d.startLineNumber === marker.startLineNumber &&
+                    d.startColumn === marker.startColumn &&
+                    d.endLineNumber === marker.endLineNumber &&
+                    d.endColumn === marker.endColumn);
+                if (!diagnostic) continue;
+                for (const fix of diagnostic.fixes) {
+                    actions.push({
+                        ...fix,
+                        diagnostics: [marker],
+                        edit: toWorkspaceEdit(fix.edit),
+                    });
+                }
+            }
+            return { actions, dispose() { } };
+        }
+    });
     monaco.languages.registerFoldingRangeProvider(modeId, {
         async provideFoldingRanges() {
             return await state.folding_ranges();
@@ -320,13 +423,19 @@ const start = async () => {
     var loadingText = document.createTextNode("Loading wasm...");
     document.body.appendChild(loadingText);    
     
-    let model = monaco.editor.createModel(exampleCode, modeId);
+    let model = monaco.editor.createModel(exampleCode, modeId, monaco.Uri.parse('file:///my_crate/main.rs'));
     window.editor = monaco.editor;
     state = null; //await createRA();
 
     async function update() {
         const res = await state.update(model.getValue());
-        monaco.editor.setModelMarkers(model, modeId, res.diagnostics);
+        const markers = res.diagnostics.map(({ fixes, code, experimental, ...marker }) => ({
+            ...marker,
+            code: { value: code.value, target: monaco.Uri.parse(code.target) },
+            source: experimental ? 'rust-analyzer (experimental)' : 'rust-analyzer',
+        }));
+        monaco.editor.setModelMarkers(model, modeId, markers);
+        allDiagnostics = res.diagnostics;
         allTokens = res.highlights;
     }
 
@@ -344,10 +453,53 @@ const start = async () => {
         ],
     });
     document.body.removeChild(loadingText);
+    // The analysis inputs are cached, keyed by the build, the state format
+    // and a hash of the sysroot sources they were built from, so that
+    // reloading the page doesn't need to run `init`.
+    const stateKey = async () => {
+        const sources = new TextEncoder().encode([fake_std, fake_core, fake_alloc].join('\0'));
+        const digest = new Uint8Array(await crypto.subtle.digest('SHA-256', sources));
+        const hash = Array.from(digest, (b) => b.toString(16).padStart(2, '0')).join('');
+        return `/ra-wasm-state/${BUILD_ID}/${await state.state_version()}/${hash}`;
+    };
+    const restoreState = async () => {
+        if (!window.caches) return false;
+        try {
+            const cache = await caches.open('ra-wasm');
+            const response = await cache.match(await stateKey());
+            if (!response) return false;
+            // The worker turns errors into `null`, `import_state` otherwise
+            // returns `undefined`.
+            const res = await state.import_state(new Uint8Array(await response.arrayBuffer()));
+            return res !== null;
+        } catch (err) {
+            console.warn('failed to restore the analysis state:', err);
+            return false;
+        }
+    };
+    // Replaces the states cached by other builds or for other sources.
+    const saveState = async () => {
+        if (!window.caches) return;
+        const key = await stateKey();
+        const bytes = await state.export_state();
+        const cache = await caches.open('ra-wasm');
+        for (const request of await cache.keys()) {
+            if (new URL(request.url).pathname !== key) await cache.delete(request);
+        }
+        await cache.put(key, new Response(bytes));
+    };
     const initRA = async () => {
         state = await createRA();
+        // E.g. `?log=warn,ra_ap_hir_def=info`, see `logger.rs` for the syntax.
+        const logFilter = new URLSearchParams(window.location.search).get('log');
+        if (logFilter) {
+            await state.set_log_level(logFilter);
+        }
         await registerRA();
-        await state.init(model.getValue(), fake_std, fake_core, fake_alloc);
+        if (!await restoreState()) {
+            await state.init(model.getValue(), fake_std, fake_core, fake_alloc);
+            saveState().catch((err) => console.warn('failed to save the analysis state:', err));
+        }
         await update();
         model.onDidChangeContent(update);
     };
@@ -357,6 +509,183 @@ const start = async () => {
         model: model
     });
 
+    // Target of the `command:` links produced for intra-doc links in hovers.
+    // Sysroot sources are opened read-only.
+    const gotoLocation = ({ uri, range }) => {
+        const target = getModel(uri);
+        if (!target) return;
+        if (myEditor.getModel() !== target) {
+            myEditor.setModel(target);
+            myEditor.updateOptions({ readOnly: target !== model });
+        }
+        myEditor.setSelection(range);
+        myEditor.revealRangeInCenter(range);
+    };
+    monaco.editor.registerCommand('rust-analyzer.gotoLocation', (_accessor, location) => gotoLocation(location));
+    // The standalone editor can't switch models by itself, so going to a
+    // definition in another file would do nothing.
+    const editorService = myEditor._codeEditorService;
+    const openCodeEditor = editorService.openCodeEditor.bind(editorService);
+    editorService.openCodeEditor = async (input, source, sideBySide) => {
+        const result = await openCodeEditor(input, source, sideBySide);
+        if (result) return result;
+        const selection = input.options && input.options.selection;
+        const range = selection && {
+            startLineNumber: selection.startLineNumber,
+            startColumn: selection.startColumn,
+            endLineNumber: selection.endLineNumber || selection.startLineNumber,
+            endColumn: selection.endColumn || selection.startColumn,
+        };
+        gotoLocation({ uri: input.resource.toString(), range: range || new monaco.Range(1, 1, 1, 1) });
+        return myEditor;
+    };
+    myEditor.addAction({
+        id: 'rust-analyzer.externalDocs',
+        label: 'Open External Documentation',
+        contextMenuGroupId: 'navigation',
+        async run(ed) {
+            const pos = ed.getPosition();
+            const url = await state.external_docs(pos.lineNumber, pos.column);
+            if (url) {
+                window.open(url, '_blank');
+            }
+        },
+    });
+
+    myEditor.addAction({
+        id: 'rust-analyzer.joinLines',
+        label: 'Join Lines',
+        keybindings: [monaco.KeyMod.CtrlCmd | monaco.KeyCode.KeyJ],
+        async run(ed) {
+            if (ed.getModel() !== model) return;
+            const edits = await state.join_lines(ed.getSelections());
+            if (edits) {
+                ed.executeEdits('rust-analyzer', edits);
+            }
+        },
+    });
+    myEditor.addAction({
+        id: 'rust-analyzer.matchingBrace',
+        label: 'Find Matching Brace',
+        keybindings: [monaco.KeyMod.CtrlCmd | monaco.KeyMod.Shift | monaco.KeyCode.Backslash],
+        async run(ed) {
+            if (ed.getModel() !== model) return;
+            const positions = await state.matching_brace(ed.getSelections().map(s => s.getPosition()));
+            if (positions) {
+                ed.setSelections(positions.map(({ lineNumber, column }) =>
+                    new monaco.Selection(lineNumber, column, lineNumber, column)));
+            }
+        },
+    });
+    myEditor.addAction({
+        id: 'rust-analyzer.ssr',
+        label: 'Structural Search Replace',
+        async run(ed) {
+            if (ed.getModel() !== model) return;
+            const query = window.prompt('Structural search replace, e.g. foo($a, $b) ==>> bar($b, $a)');
+            if (!query) return;
+            const res = await state.structural_search_replace(query, false);
+            if (!res) return;
+            if (res.error) {
+                window.alert(`${res.error.kind === 'parse' ? 'Parse error' : 'Error'}: ${res.error.message}`);
+                return;
+            }
+            const edits = toWorkspaceEdit(res.edit).edits
+                .filter(edit => edit.resource && edit.resource.toString() === model.uri.toString())
+                .map(({ edit }) => edit);
+            ed.executeEdits('rust-analyzer', edits);
+        },
+    });
+    for (const direction of ['up', 'down']) {
+        myEditor.addAction({
+            id: `rust-analyzer.moveItem.${direction}`,
+            label: `Move Item ${direction === 'up' ? 'Up' : 'Down'}`,
+            keybindings: [monaco.KeyMod.Alt | (direction === 'up' ? monaco.KeyCode.UpArrow : monaco.KeyCode.DownArrow)],
+            async run(ed) {
+                const edits = ed.getModel() === model
+                    ? await state.move_item(ed.getSelection(), direction)
+                    : null;
+                if (!edits) {
+                    // Not on an item, move the lines as Monaco does by default.
+                    ed.trigger('keyboard', direction === 'up' ? 'editor.action.moveLinesUpAction' : 'editor.action.moveLinesDownAction');
+                    return;
+                }
+                // Monaco cannot apply snippet edits directly, drop the cursor marker.
+                ed.executeEdits('rust-analyzer', edits.map(({ range, text }) => ({ range, text: text.replace('$0', '') })));
+            },
+        });
+    }
+    myEditor.addAction({
+        id: 'rust-analyzer.parentModule',
+        label: 'Locate Parent Module',
+        contextMenuGroupId: 'navigation',
+        async run(ed) {
+            if (ed.getModel() !== model) return;
+            const pos = ed.getPosition();
+            const locations = await state.parent_module(pos.lineNumber, pos.column);
+            if (locations && locations.length > 0) {
+                gotoLocation(locations[0]);
+            }
+        },
+    });
+    myEditor.addAction({
+        id: 'rust-analyzer.viewCrateGraph',
+        label: 'View Crate Graph',
+        async run() {
+            const graph = await state.view_crate_graph(true);
+            if (graph) {
+                openText(graph.dot);
+            }
+        },
+    });
+    myEditor.addAction({
+        id: 'rust-analyzer.status',
+        label: 'Show Analysis Status',
+        async run() {
+            const status = await state.status();
+            if (!status) return;
+            const queries = status.queries.map(({ name, count, totalMs, maxMs }) =>
+                `${name.padEnd(28)} ${String(count).padStart(6)} calls ${totalMs.toFixed(1).padStart(10)} ms total ${maxMs.toFixed(1).padStart(8)} ms max`);
+            openText([
+                `${status.files} files, ${status.crates} crates`,
+                `wasm memory: ${(status.memoryBytes / (1024 * 1024)).toFixed(1)} MiB`,
+                '',
+                status.analysis,
+                '',
+                ...queries,
+            ].join('\n'));
+        },
+    });
+    let tracing = false;
+    myEditor.addAction({
+        id: 'rust-analyzer.toggleTracing',
+        label: 'Start/Stop Tracing',
+        async run() {
+            tracing = !tracing;
+            if (tracing) {
+                await state.set_tracing(true);
+                return;
+            }
+            // Load the file in the performance panel of the browser's dev tools.
+            downloadText('rust-analyzer-trace.json', await state.export_trace());
+            await state.set_tracing(false);
+        },
+    });
+    // Continues doc comments on Enter, falling back to a plain newline.
+    myEditor.addCommand(monaco.KeyCode.Enter, async () => {
+        const pos = myEditor.getPosition();
+        const edits = myEditor.getModel() === model && myEditor.getSelection().isEmpty()
+            ? await state.on_enter(pos.lineNumber, pos.column)
+            : null;
+        if (!edits || edits.length !== 1) {
+            myEditor.trigger('keyboard', 'type', { text: '\n' });
+            return;
+        }
+        const [{ range, text }] = edits;
+        myEditor.setSelection(range);
+        myEditor.getContribution('snippetController2').insert(text);
+    }, 'editorTextFocus && !suggestWidgetVisible && !renameInputVisible && !inSnippetMode');
+
     window.onresize = () => myEditor.layout();
 };
 
@@ -11,7 +11,12 @@ const start = async () => {
     
     onmessage = (e) => {
         const { which, args, id } = e.data;
-        const result = state[which](...args);
+        let result = null;
+        try {
+            result = state[which](...args);
+        } catch (err) {
+            console.error(`${which}:`, err);
+        }
 
         postMessage({
             id: id,
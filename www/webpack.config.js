@@ -1,4 +1,5 @@
 const path = require("path");
+const webpack = require("webpack");
 const HtmlWebPackPlugin = require("html-webpack-plugin");
 
 module.exports = {
@@ -30,6 +31,10 @@ module.exports = {
         ],
     },
     plugins: [
+        // Identifies the build in the key of the cached analysis state.
+        new webpack.DefinePlugin({
+            BUILD_ID: JSON.stringify(Date.now().toString(36)),
+        }),
         new HtmlWebPackPlugin({
             title: "Rust Analyzer Playground",
             chunks: ["app"],